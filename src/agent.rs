@@ -0,0 +1,739 @@
+//! Optional unlock agent: a background process that keeps the decrypted
+//! database in memory and answers lookups over a Unix domain socket, so the
+//! KDF and the password source only run once per agent lifetime.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::NaiveDateTime;
+use keepass::db::{Entry, History, Times, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::output::TIME_FORMAT;
+use crate::placeholder::Fields;
+use crate::search::{SearchOptions, SearchResult, UrlQuery};
+
+/// Idle time after which the agent locks itself, unless configured.
+pub const DEFAULT_IDLE_TIMEOUT: u64 = 900;
+/// Maximum lifetime of an agent, unless configured.
+pub const DEFAULT_LIFETIME: u64 = 8 * 3600;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Find {
+        db_path: String,
+        query: String,
+        options: SearchOptions,
+        /// Fields whose references get resolved.
+        #[serde(default)]
+        fields: Fields,
+        /// Include previous versions, only sent for `--history` lookups.
+        #[serde(default)]
+        history: bool,
+    },
+    FindUrl {
+        db_path: String,
+        query: UrlQuery,
+        #[serde(default)]
+        fields: Fields,
+    },
+    Lock,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Response {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// `None` when the agent holds a different database than requested.
+    #[serde(default)]
+    results: Option<Vec<WireResult>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WireResult {
+    path: String,
+    uuid: String,
+    fields: Vec<WireField>,
+    tags: Vec<String>,
+    expires: bool,
+    times: HashMap<String, String>,
+    /// Previous versions, in the order of the entry history.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    history: Vec<WireResult>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    matched: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WireField {
+    name: String,
+    value: String,
+    protected: bool,
+}
+
+impl WireResult {
+    fn from_result(result: &SearchResult, history: bool) -> Self {
+        Self {
+            matched: result.matched.clone(),
+            ..Self::from_entry(&result.path, &result.entry, history)
+        }
+    }
+
+    fn from_entry(path: &str, entry: &Entry, history: bool) -> Self {
+        let fields = entry
+            .fields
+            .iter()
+            .filter_map(|(name, value)| {
+                let protected = matches!(value, Value::Protected(_));
+                entry.get(name).map(|v| WireField {
+                    name: name.clone(),
+                    value: v.to_string(),
+                    protected,
+                })
+            })
+            .collect();
+        Self {
+            path: path.to_string(),
+            uuid: entry.uuid.to_string(),
+            fields,
+            tags: entry.tags.clone(),
+            expires: entry.times.expires.unwrap_or(false),
+            times: time_fields(&mut entry.times.clone())
+                .into_iter()
+                .filter_map(|(k, v)| {
+                    Some((k.to_string(), v.as_ref()?.format(TIME_FORMAT).to_string()))
+                })
+                .collect(),
+            history: entry
+                .history
+                .iter()
+                .filter(|_| history)
+                .flat_map(|h| h.get_entries())
+                .map(|e| Self::from_entry(path, e, false))
+                .collect(),
+            matched: Vec::new(),
+        }
+    }
+
+    fn into_result(self) -> Result<SearchResult> {
+        let mut entry = Entry {
+            uuid: self.uuid.parse().context("Invalid UUID from agent")?,
+            tags: self.tags,
+            times: Times::default(),
+            ..Default::default()
+        };
+        entry.times.expires = Some(self.expires);
+        for (key, time) in time_fields(&mut entry.times) {
+            if let Some(value) = self.times.get(key) {
+                *time = Some(
+                    NaiveDateTime::parse_from_str(value, TIME_FORMAT)
+                        .context("Invalid timestamp from agent")?,
+                );
+            }
+        }
+        for field in self.fields {
+            let value = if field.protected {
+                crate::edit::protected(&field.value)
+            } else {
+                crate::edit::unprotected(&field.value)
+            };
+            entry.fields.insert(field.name, value);
+        }
+        if !self.history.is_empty() {
+            let mut history = History::default();
+            // add_entry inserts at the front
+            for item in self.history.into_iter().rev() {
+                history.add_entry(item.into_result()?.entry);
+            }
+            entry.history = Some(history);
+        }
+        Ok(SearchResult {
+            path: self.path,
+            entry,
+            matched: self.matched,
+        })
+    }
+}
+
+/// Entry timestamps by their KeePass names, as sent over the socket.
+fn time_fields(times: &mut Times) -> [(&'static str, &mut Option<NaiveDateTime>); 5] {
+    [
+        ("CreationTime", &mut times.creation),
+        ("LastModificationTime", &mut times.last_modification),
+        ("LastAccessTime", &mut times.last_access),
+        ("ExpiryTime", &mut times.expiry),
+        ("LocationChanged", &mut times.location_changed),
+    ]
+}
+
+/// Socket location: `KPASSCLI_AGENT_SOCKET`, else a per-user runtime directory.
+pub fn socket_path() -> PathBuf {
+    if let Ok(path) = std::env::var("KPASSCLI_AGENT_SOCKET") {
+        return PathBuf::from(path);
+    }
+    let dir = match dirs::runtime_dir() {
+        Some(dir) => dir.join("kpasscli"),
+        None => std::env::temp_dir().join(format!("kpasscli-{}", current_uid())),
+    };
+    dir.join("agent.sock")
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and cannot fail
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(unix))]
+fn current_uid() -> u32 {
+    0
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::os::unix::process::CommandExt;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant, SystemTime};
+
+    use crate::db_helper::{open_database, Credentials, Session};
+    use crate::placeholder::Resolver;
+    use crate::search::Finder;
+
+    /// Everything the background agent needs, passed over its stdin.
+    #[derive(Serialize, Deserialize)]
+    struct Handoff {
+        db_path: String,
+        password: Option<String>,
+        keyfile: Option<String>,
+    }
+
+    /// How long a client may take to send its request or read the reply.
+    const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn send(request: &Request) -> Result<Option<Response>> {
+        let path = socket_path();
+        let mut stream = match UnixStream::connect(&path) {
+            Ok(stream) => stream,
+            Err(_) => return Ok(None),
+        };
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes())?;
+
+        let mut reply = String::new();
+        BufReader::new(stream)
+            .read_line(&mut reply)
+            .context("Failed to read reply from agent")?;
+        Ok(Some(serde_json::from_str(&reply)?))
+    }
+
+    /// Looks up `query` through a running agent. Returns `None` when no agent
+    /// is running or it holds a different database.
+    /// References are resolved in `fields` only, and the entry history is
+    /// only included when `history` is set.
+    pub fn find(
+        db_path: &str,
+        query: &str,
+        options: &SearchOptions,
+        fields: &Fields,
+        history: bool,
+    ) -> Result<Option<Vec<SearchResult>>> {
+        let request = Request::Find {
+            db_path: canonical(db_path),
+            query: query.to_string(),
+            options: options.clone(),
+            fields: fields.clone(),
+            history,
+        };
+        results_of(send(&request)?)
+    }
+
+    /// Looks up credentials for a URL through a running agent, like `find`.
+    pub fn find_url(
+        db_path: &str,
+        query: &UrlQuery,
+        fields: &Fields,
+    ) -> Result<Option<Vec<SearchResult>>> {
+        let request = Request::FindUrl {
+            db_path: canonical(db_path),
+            query: query.clone(),
+            fields: fields.clone(),
+        };
+        results_of(send(&request)?)
+    }
+
+    fn results_of(response: Option<Response>) -> Result<Option<Vec<SearchResult>>> {
+        let response = match response {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+        if let Some(error) = response.error {
+            return Err(anyhow!(error));
+        }
+        match response.results {
+            Some(results) => results
+                .into_iter()
+                .map(WireResult::into_result)
+                .collect::<Result<Vec<_>>>()
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Asks a running agent to drop the database and exit.
+    pub fn lock() -> Result<bool> {
+        Ok(send(&Request::Lock)?.is_some())
+    }
+
+    /// Starts a detached agent process holding the already unlocked session.
+    pub fn spawn(session: &Session, idle_timeout: u64, lifetime: u64) -> Result<()> {
+        if UnixStream::connect(socket_path()).is_ok() {
+            return Err(anyhow!(
+                "An agent is already running at {:?}; run 'kpasscli lock' first",
+                socket_path()
+            ));
+        }
+
+        let exe = std::env::current_exe().context("Failed to get current executable path")?;
+        let mut child = Command::new(exe)
+            .arg("agent")
+            .arg("--serve-stdin")
+            .arg("--idle-timeout")
+            .arg(idle_timeout.to_string())
+            .arg("--lifetime")
+            .arg(lifetime.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()
+            .context("Failed to spawn agent process")?;
+
+        let handoff = Handoff {
+            db_path: canonical(&session.path),
+            password: session.credentials.password.clone(),
+            keyfile: session.credentials.keyfile.clone(),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(serde_json::to_string(&handoff)?.as_bytes())?;
+        }
+
+        let mut status = String::new();
+        if let Some(stdout) = child.stdout.take() {
+            BufReader::new(stdout).read_line(&mut status)?;
+        }
+        match status.trim() {
+            "ok" => Ok(()),
+            "" => Err(anyhow!("Agent exited during startup")),
+            error => Err(anyhow!("Agent failed to start: {}", error)),
+        }
+    }
+
+    /// Entry point of the background process started by `spawn`.
+    pub fn serve_from_stdin(idle_timeout: u64, lifetime: u64) -> Result<()> {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        let handoff: Handoff = serde_json::from_str(&input)?;
+
+        let startup = (|| -> Result<(Session, UnixListener)> {
+            let credentials = Credentials {
+                password: handoff.password,
+                keyfile: handoff.keyfile,
+            };
+            let db = open_database(&handoff.db_path, &credentials)?;
+            let session = Session {
+                db,
+                path: handoff.db_path,
+                credentials,
+            };
+            Ok((session, bind_socket(&socket_path())?))
+        })();
+
+        let (session, listener) = match startup {
+            Ok(started) => {
+                println!("ok");
+                started
+            }
+            Err(e) => {
+                println!("{:#}", e);
+                return Err(e);
+            }
+        };
+        serve(session, listener, idle_timeout, lifetime)
+    }
+
+    /// Binds a listening socket at `path`, accessible to the current user only.
+    ///
+    /// The directory containing the socket is created if needed and has to be
+    /// owned by the current user with mode 0700; otherwise another user could
+    /// have created it to control the socket. The socket itself is created
+    /// with mode 0600.
+    pub fn bind_socket(path: &Path) -> Result<UnixListener> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Failed to create socket directory: {:?}", dir))?;
+        check_private_dir(dir)?;
+
+        // A socket file without a listener is left over from a killed agent
+        if fs::symlink_metadata(path).is_ok() && UnixStream::connect(path).is_err() {
+            fs::remove_file(path)?;
+        }
+        // SAFETY: umask has no preconditions; the previous mask is restored
+        // right after binding
+        let previous = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(path);
+        unsafe { libc::umask(previous) };
+        listener.with_context(|| format!("Failed to bind socket: {:?}", path))
+    }
+
+    fn check_private_dir(dir: &Path) -> Result<()> {
+        let metadata = fs::symlink_metadata(dir)
+            .with_context(|| format!("Failed to inspect socket directory: {:?}", dir))?;
+        if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o777 != 0o700
+        {
+            return Err(anyhow!(
+                "Refusing to use socket directory {:?}: it must be a directory owned by the current user with mode 0700",
+                dir
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the process at the other end of `stream` runs as the current user.
+    pub fn peer_is_owner(stream: &UnixStream) -> bool {
+        peer_uid(stream).is_some_and(|uid| uid == current_uid())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn peer_uid(stream: &UnixStream) -> Option<u32> {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: cred and len describe a writable ucred of the given size
+        let rc = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        (rc == 0).then_some(cred.uid)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn peer_uid(stream: &UnixStream) -> Option<u32> {
+        let mut uid = 0;
+        let mut gid = 0;
+        // SAFETY: uid and gid are valid for writes
+        let rc = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+        (rc == 0).then_some(uid)
+    }
+
+    fn shutdown() -> ! {
+        let _ = fs::remove_file(socket_path());
+        std::process::exit(0);
+    }
+
+    fn serve(
+        session: Session,
+        listener: UnixListener,
+        idle_timeout: u64,
+        lifetime: u64,
+    ) -> Result<()> {
+        let started = Instant::now();
+        let last_activity = Arc::new(Mutex::new(Instant::now()));
+
+        let watchdog_activity = Arc::clone(&last_activity);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let idle = watchdog_activity
+                .lock()
+                .map(|t| t.elapsed())
+                .unwrap_or_default();
+            if idle >= Duration::from_secs(idle_timeout)
+                || started.elapsed() >= Duration::from_secs(lifetime)
+            {
+                shutdown();
+            }
+        });
+
+        let mut session = session;
+        let mut modified = modified_time(&session.path);
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            if let Ok(mut t) = last_activity.lock() {
+                *t = Instant::now();
+            }
+
+            // Pick up changes written by add/edit/mv/rm since the last request
+            let current = modified_time(&session.path);
+            if current != modified {
+                if let Ok(db) = open_database(&session.path, &session.credentials) {
+                    session.db = db;
+                    modified = current;
+                }
+            }
+
+            let _ = handle(&session, stream);
+        }
+        Ok(())
+    }
+
+    pub(super) fn handle(session: &Session, stream: UnixStream) -> Result<()> {
+        if !peer_is_owner(&stream) {
+            return Err(anyhow!("Connection from another user"));
+        }
+        // Requests are answered one at a time; a stalled client must not
+        // block the agent
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Lock) => {
+                reply(&stream, &Response::default())?;
+                shutdown();
+            }
+            Ok(Request::Find {
+                db_path,
+                query,
+                options,
+                fields,
+                history,
+            }) => {
+                if db_path != session.path {
+                    Response::default()
+                } else {
+                    let finder = Finder::new(&session.db, options);
+                    let resolver = Resolver::new(&session.db);
+                    match finder
+                        .find(&query)
+                        .map(|results| resolver.resolve_results(results, &fields, history))
+                    {
+                        Ok(results) => Response {
+                            error: None,
+                            results: Some(
+                                results
+                                    .iter()
+                                    .map(|r| WireResult::from_result(r, history))
+                                    .collect(),
+                            ),
+                        },
+                        Err(e) => Response {
+                            error: Some(format!("{:#}", e)),
+                            results: None,
+                        },
+                    }
+                }
+            }
+            Ok(Request::FindUrl {
+                db_path,
+                query,
+                fields,
+            }) => {
+                if db_path != session.path {
+                    Response::default()
+                } else {
+                    let finder = Finder::new(&session.db, SearchOptions::default());
+                    let results = finder.find_by_url(&query);
+                    let results =
+                        Resolver::new(&session.db).resolve_results(results, &fields, false);
+                    Response {
+                        error: None,
+                        results: Some(
+                            results
+                                .iter()
+                                .map(|r| WireResult::from_result(r, false))
+                                .collect(),
+                        ),
+                    }
+                }
+            }
+            Err(e) => Response {
+                error: Some(format!("Invalid request: {}", e)),
+                results: None,
+            },
+        };
+        reply(&stream, &response)
+    }
+
+    fn reply(mut stream: &UnixStream, response: &Response) -> Result<()> {
+        let mut line = serde_json::to_string(response)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn modified_time(path: &str) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    fn canonical(path: &str) -> String {
+        fs::canonicalize(path)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| path.to_string())
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{bind_socket, find, find_url, lock, peer_is_owner, serve_from_stdin, spawn};
+
+#[cfg(not(unix))]
+pub fn find(
+    _db_path: &str,
+    _query: &str,
+    _options: &SearchOptions,
+    _fields: &Fields,
+    _history: bool,
+) -> Result<Option<Vec<SearchResult>>> {
+    Ok(None)
+}
+
+#[cfg(not(unix))]
+pub fn find_url(
+    _db_path: &str,
+    _query: &UrlQuery,
+    _fields: &Fields,
+) -> Result<Option<Vec<SearchResult>>> {
+    Ok(None)
+}
+
+#[cfg(not(unix))]
+pub fn lock() -> Result<bool> {
+    Ok(false)
+}
+
+#[cfg(not(unix))]
+pub fn spawn(
+    _session: &crate::db_helper::Session,
+    _idle_timeout: u64,
+    _lifetime: u64,
+) -> Result<()> {
+    Err(anyhow!("The agent is only supported on Unix systems"))
+}
+
+#[cfg(not(unix))]
+pub fn serve_from_stdin(_idle_timeout: u64, _lifetime: u64) -> Result<()> {
+    Err(anyhow!("The agent is only supported on Unix systems"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::db_helper::{Credentials, Session};
+    use crate::edit::{create_entry, update_entry, FieldChanges};
+    use keepass::Database;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+
+    fn session() -> Session {
+        let mut db = Database::new(Default::default());
+        let changes = FieldChanges {
+            set: vec![("UserName", "admin"), ("Password", "old")],
+            ..Default::default()
+        };
+        create_entry(&mut db, "/db01", &changes).unwrap();
+        let uuid = db.root.entries[0].uuid;
+        let changes = FieldChanges {
+            set: vec![("Password", "new")],
+            ..Default::default()
+        };
+        update_entry(&mut db, &uuid, &changes).unwrap();
+        Session {
+            db,
+            path: "/tmp/test.kdbx".to_string(),
+            credentials: Credentials::default(),
+        }
+    }
+
+    fn round_trip(session: &Session, request: &Request) -> Response {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        let mut line = serde_json::to_string(request).unwrap();
+        line.push('\n');
+        client.write_all(line.as_bytes()).unwrap();
+        unix::handle(session, server).unwrap();
+
+        let mut reply = String::new();
+        BufReader::new(client).read_line(&mut reply).unwrap();
+        serde_json::from_str(&reply).unwrap()
+    }
+
+    fn find(history: bool) -> Request {
+        Request::Find {
+            db_path: "/tmp/test.kdbx".to_string(),
+            query: "db01".to_string(),
+            options: SearchOptions::default(),
+            fields: Fields::All,
+            history,
+        }
+    }
+
+    #[test]
+    fn test_find_round_trip() {
+        let session = session();
+        let results = round_trip(&session, &find(false)).results.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].history.is_empty());
+
+        let result = results.into_iter().next().unwrap().into_result().unwrap();
+        assert_eq!(result.path, "/db01");
+        assert_eq!(result.entry.get_username(), Some("admin"));
+        assert_eq!(result.entry.get_password(), Some("new"));
+        assert!(matches!(
+            result.entry.fields.get("Password"),
+            Some(Value::Protected(_))
+        ));
+
+        let results = round_trip(&session, &find(true)).results.unwrap();
+        let result = results.into_iter().next().unwrap().into_result().unwrap();
+        let history = result.entry.history.unwrap();
+        assert_eq!(history.get_entries()[0].get_password(), Some("old"));
+
+        let other = Request::Find {
+            db_path: "/tmp/other.kdbx".to_string(),
+            query: "db01".to_string(),
+            options: SearchOptions::default(),
+            fields: Fields::All,
+            history: false,
+        };
+        assert!(round_trip(&session, &other).results.is_none());
+    }
+
+    #[test]
+    fn test_bind_socket_requires_private_dir() {
+        let dir = std::env::temp_dir().join(format!("kpasscli-test-{}-socket", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let socket = dir.join("agent.sock");
+        assert!(unix::bind_socket(&socket).is_err());
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        let _listener = unix::bind_socket(&socket).unwrap();
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
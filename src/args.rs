@@ -1,16 +1,22 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+use crate::output::OutputFormat;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
     /// Path to KeePass database file
-    #[arg(short = 'p', long = "kdbpath", alias = "p")]
+    #[arg(short = 'p', long = "kdbpath", alias = "p", global = true)]
     pub kdb_path: Option<String>,
 
     /// Password file or executable to get password
-    #[arg(short = 'w', long = "kdbpassword", alias = "w")]
+    #[arg(short = 'w', long = "kdbpassword", alias = "w", global = true)]
     pub kdb_password: Option<String>,
 
+    /// Key file used (alone or together with the password) to open the database
+    #[arg(short = 'k', long = "keyfile", global = true)]
+    pub keyfile: Option<String>,
+
     /// Item to search for
     #[arg(short = 'i', long = "item", alias = "i")]
     pub item: Option<String>,
@@ -20,27 +26,60 @@ pub struct Args {
     pub field_name: String,
 
     /// Output type (clipboard/stdout)
-    #[arg(short = 'o', long = "out")]
+    #[arg(short = 'o', long = "out", global = true)]
     pub out: Option<String>,
 
     /// Enable exact match search
-    #[arg(short = 'C', long = "Clip")]
+    #[arg(short = 'C', long = "Clip", global = true)]
     pub clipboard: bool,
 
     /// Enable case-sensitive search
-    #[arg(short = 'c', long = "case-sensitive", alias = "cs")]
+    #[arg(short = 'c', long = "case-sensitive", alias = "cs", global = true)]
     pub case_sensitive: bool,
 
     /// Enable exact match search
-    #[arg(short = 'e', long = "exact-match")]
+    #[arg(short = 'e', long = "exact-match", global = true)]
     pub exact_match: bool,
 
+    /// Treat the search text and absolute path segments as regular expressions
+    #[arg(long = "regex", conflicts_with = "glob", global = true)]
+    pub regex: bool,
+
+    /// Treat the search text and absolute path segments as globs (`**` matches any number of groups)
+    #[arg(long = "glob", global = true)]
+    pub glob: bool,
+
+    /// Match when the characters of the search text appear in order
+    #[arg(long = "fuzzy", conflicts_with_all = ["regex", "glob", "exact_match"], global = true)]
+    pub fuzzy: bool,
+
+    /// Use the best match when several entries are found
+    #[arg(long = "first", conflicts_with = "index", global = true)]
+    pub first: bool,
+
+    /// Use the Nth match (as numbered in the list of matches) when several entries are found
+    #[arg(long = "index", value_name = "N", global = true)]
+    pub index: Option<usize>,
+
+    /// Only entries with these tags: `a,b` needs one of them, `!a` excludes a tag (repeatable, all have to match)
+    #[arg(long = "tag", value_name = "TAGS")]
+    pub tags: Vec<String>,
+
+    /// Fields to search instead of the title (title, username, url, notes, tags, all or custom field names)
+    #[arg(
+        long = "in",
+        value_name = "FIELDS",
+        value_delimiter = ',',
+        global = true
+    )]
+    pub search_in: Vec<String>,
+
     /// Show manual page
     #[arg(short = 'm', long = "man")]
     pub show_man: bool,
 
     /// Enable debug logging
-    #[arg(short = 'd', long = "debug")]
+    #[arg(short = 'd', long = "debug", global = true)]
     pub debug: bool,
 
     /// Enable verify messages
@@ -56,22 +95,454 @@ pub struct Args {
     pub print_config: bool,
 
     /// Path to configuration file
-    #[arg(long = "config", default_value = "~/.config/kpasscli/config.yaml")]
+    #[arg(
+        long = "config",
+        default_value = "~/.config/kpasscli/config.yaml",
+        global = true
+    )]
     pub config_path: String,
 
     /// Show all fields
     #[arg(long, help = "Show all fields of an entry")]
     pub show_all: bool,
 
+    /// Refuse expired entries instead of warning about them
+    #[arg(long = "strict-expiry", global = true)]
+    pub strict_expiry: bool,
+
+    /// Read from the Nth previous version of the entry (1 = the latest one)
+    #[arg(long = "history", value_name = "N")]
+    pub history: Option<usize>,
+
+    /// Output format for entries and search results
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        ignore_case = true,
+        global = true
+    )]
+    pub format: OutputFormat,
+
+    /// Include protected field values in structured output
+    #[arg(long = "reveal", global = true)]
+    pub reveal: bool,
+
     /// Hidden argument for background clipboard clearing (internal use only)
     #[arg(long, hide = true)]
     pub clear_clipboard_after: Option<u64>,
 
-    /// Get TOTP token
+    /// Get TOTP token (HOTP entries save the next counter, which opens the
+    /// database even when the agent is running)
     #[arg(short = 't', long = "totp")]
     pub totp: bool,
 
     /// Get password and TOTP token
     #[arg(short = 'T', long = "password-totp", alias = "pt")]
     pub password_totp: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Create a new entry, creating intermediate groups as needed
+    Add(AddArgs),
+    /// Set or unset fields of an existing entry
+    Edit(EditArgs),
+    /// Move an entry into another group
+    Mv(MvArgs),
+    /// Move an entry to the Recycle Bin, or delete it permanently
+    Rm(RmArgs),
+    /// Generate a password or passphrase
+    Generate(GenerateArgs),
+    /// List the subgroups and entries of a group
+    Ls(LsArgs),
+    /// Report reused, weak, empty and old passwords without printing them
+    Audit(AuditArgs),
+    /// Check passwords against a local Have I Been Pwned hash list
+    Hibp(HibpArgs),
+    /// Report entries that expired or expire soon (exit code 1: expiring, 2: expired)
+    Expiring(ExpiringArgs),
+    /// Show the group hierarchy as a tree
+    Tree(TreeArgs),
+    /// List the previous versions of an entry and the fields they changed
+    History(HistoryArgs),
+    /// List, extract, attach or remove the attachments of an entry
+    Attachments(AttachmentsArgs),
+    /// Run a command with secrets from the database in its environment
+    Exec(ExecArgs),
+    /// Render a template, replacing secret references with entry values
+    Inject(InjectArgs),
+    /// Start an agent that keeps the unlocked database in memory
+    Agent(AgentArgs),
+    /// Stop the running agent and drop the unlocked database
+    Lock,
+    /// Act as a git credential helper (get, store, erase)
+    #[command(name = "git-credential")]
+    GitCredential(GitCredentialArgs),
+    /// Act as a docker credential helper (get, store, erase, list)
+    #[command(name = "docker-credential")]
+    DockerCredential(DockerCredentialArgs),
+    /// Serve SSH keys stored as entry attachments over the ssh-agent protocol
+    #[command(name = "ssh-agent")]
+    SshAgent(SshAgentArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AddArgs {
+    /// Path of the new entry, e.g. /Servers/prod/db01
+    pub path: String,
+
+    /// User name of the entry
+    #[arg(short = 'u', long = "username")]
+    pub username: Option<String>,
+
+    /// URL of the entry
+    #[arg(long = "url")]
+    pub url: Option<String>,
+
+    /// Notes of the entry
+    #[arg(long = "notes")]
+    pub notes: Option<String>,
+
+    /// Password file or executable providing the entry password
+    #[arg(long = "password-from", conflicts_with = "password_stdin")]
+    pub password_from: Option<String>,
+
+    /// Read the entry password from the first line of stdin
+    #[arg(long = "password-stdin")]
+    pub password_stdin: bool,
+
+    /// Generate the entry password (see the generate command for options)
+    #[arg(long = "generate", conflicts_with_all = ["password_from", "password_stdin"])]
+    pub generate: bool,
+
+    #[command(flatten)]
+    pub generator: GeneratorArgs,
+
+    /// Custom field as NAME=VALUE (repeatable)
+    #[arg(long = "field", value_name = "NAME=VALUE")]
+    pub fields: Vec<String>,
+
+    /// Store the named custom field as protected (repeatable)
+    #[arg(long = "protect", value_name = "NAME")]
+    pub protect: Vec<String>,
+
+    /// Tag the entry (repeatable or comma-separated)
+    #[arg(long = "tag", value_name = "TAG", value_delimiter = ',')]
+    pub tags: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct EditArgs {
+    /// Entry to edit (same syntax as --item)
+    pub entry: String,
+
+    /// Set a field as NAME=VALUE (repeatable)
+    #[arg(long = "set", value_name = "NAME=VALUE")]
+    pub set: Vec<String>,
+
+    /// Remove a custom field or clear a standard field (repeatable)
+    #[arg(long = "unset", value_name = "NAME")]
+    pub unset: Vec<String>,
+
+    /// Store the named field as protected (repeatable)
+    #[arg(long = "protect", value_name = "NAME")]
+    pub protect: Vec<String>,
+
+    /// Add a tag (repeatable or comma-separated)
+    #[arg(long = "tag", value_name = "TAG", value_delimiter = ',')]
+    pub tags: Vec<String>,
+
+    /// Remove a tag (repeatable or comma-separated)
+    #[arg(long = "untag", value_name = "TAG", value_delimiter = ',')]
+    pub untags: Vec<String>,
+
+    /// Password file or executable providing the new entry password
+    #[arg(long = "password-from", conflicts_with = "password_stdin")]
+    pub password_from: Option<String>,
+
+    /// Read the new entry password from the first line of stdin
+    #[arg(long = "password-stdin")]
+    pub password_stdin: bool,
+
+    /// Generate the entry password (see the generate command for options)
+    #[arg(long = "generate", conflicts_with_all = ["password_from", "password_stdin"])]
+    pub generate: bool,
+
+    #[command(flatten)]
+    pub generator: GeneratorArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct MvArgs {
+    /// Entry to move (same syntax as --item)
+    pub entry: String,
+
+    /// Destination group path, created if it does not exist
+    pub group: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RmArgs {
+    /// Entry to remove (same syntax as --item)
+    pub entry: String,
+
+    /// Delete permanently instead of moving to the Recycle Bin
+    #[arg(long = "force")]
+    pub force: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct LsArgs {
+    /// Group to list (default: root group)
+    #[arg(default_value = "/")]
+    pub group: String,
+
+    /// List subgroups recursively
+    #[arg(short = 'R', long = "recursive")]
+    pub recursive: bool,
+
+    /// Maximum recursion depth (implies --recursive)
+    #[arg(long = "depth")]
+    pub depth: Option<usize>,
+
+    /// Include the Recycle Bin
+    #[arg(long = "recycle-bin")]
+    pub recycle_bin: bool,
+
+    /// Show user name, URL and modification time of entries
+    #[arg(short = 'l', long = "long")]
+    pub long: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AuditArgs {
+    /// Report passwords with less estimated entropy (bits)
+    #[arg(long = "min-entropy", value_name = "BITS")]
+    pub min_entropy: Option<f64>,
+
+    /// Report passwords unchanged for more days
+    #[arg(long = "max-age", value_name = "DAYS")]
+    pub max_age: Option<u32>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct HibpArgs {
+    /// Sorted HASH:COUNT file or directory of range files (default: hibp_path from the config)
+    pub list: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExpiringArgs {
+    /// Only entries in this group and its subgroups
+    #[arg(default_value = "/")]
+    pub group: String,
+
+    /// Also report entries expiring within this many days
+    #[arg(long = "days", default_value_t = crate::expiry::DEFAULT_DAYS)]
+    pub days: u32,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TreeArgs {
+    /// Group to start from (default: root group)
+    #[arg(default_value = "/")]
+    pub group: String,
+
+    /// Maximum depth to descend
+    #[arg(long = "depth")]
+    pub depth: Option<usize>,
+
+    /// Include the Recycle Bin
+    #[arg(long = "recycle-bin")]
+    pub recycle_bin: bool,
+
+    /// Show user name, URL and modification time of entries
+    #[arg(short = 'l', long = "long")]
+    pub long: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct HistoryArgs {
+    /// Entry (same syntax as --item)
+    pub entry: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AttachmentsArgs {
+    /// Entry (same syntax as --item)
+    pub entry: String,
+
+    /// Attachment to extract or remove, or the name to attach a file under;
+    /// all attachments are listed if omitted
+    pub name: Option<String>,
+
+    /// Write the attachment here (mode 0600) instead of stdout
+    #[arg(
+        short = 'O',
+        long = "output",
+        value_name = "PATH",
+        requires = "name",
+        conflicts_with_all = ["attach", "remove"]
+    )]
+    pub output: Option<String>,
+
+    /// Attach a local file, named after the file unless a name is given
+    #[arg(long = "attach", value_name = "FILE", conflicts_with = "remove")]
+    pub attach: Option<String>,
+
+    /// Remove the named attachment from the entry
+    #[arg(long = "remove", requires = "name")]
+    pub remove: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExecArgs {
+    /// Environment variable as NAME=ENTRY[:FIELD], FIELD defaults to Password (repeatable)
+    #[arg(long = "env", value_name = "NAME=ENTRY[:FIELD]")]
+    pub env: Vec<String>,
+
+    /// File with one NAME=ENTRY[:FIELD] mapping per line (repeatable)
+    #[arg(long = "env-file", value_name = "PATH")]
+    pub env_file: Vec<String>,
+
+    /// Command and its arguments
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    pub command: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct InjectArgs {
+    /// Template file, or - for stdin
+    pub template: String,
+
+    /// Write the rendered file here (mode 0600) instead of stdout
+    #[arg(short = 'O', long = "output", value_name = "PATH")]
+    pub output: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AgentArgs {
+    /// Lock after this many seconds without requests
+    #[arg(long = "idle-timeout")]
+    pub idle_timeout: Option<u64>,
+
+    /// Lock after this many seconds regardless of activity
+    #[arg(long = "lifetime")]
+    pub lifetime: Option<u64>,
+
+    /// Hidden argument for the background agent process (internal use only)
+    #[arg(long, hide = true)]
+    pub serve_stdin: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GitCredentialArgs {
+    /// Operation requested by git: get, store or erase
+    pub action: String,
+
+    /// Group for entries created by store (default: /Git)
+    #[arg(long = "group")]
+    pub group: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DockerCredentialArgs {
+    /// Operation requested by docker: get, store, erase or list
+    pub action: String,
+
+    /// Group holding the registry entries (default: /Registries)
+    #[arg(long = "group")]
+    pub group: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SshAgentArgs {
+    /// Load keys from entries in this group and its subgroups
+    #[arg(long = "group")]
+    pub group: Option<String>,
+
+    /// Load keys from entries with this tag
+    #[arg(long = "tag")]
+    pub tag: Option<String>,
+
+    /// Ask for confirmation through SSH_ASKPASS before each use of a key
+    #[arg(long = "confirm")]
+    pub confirm: bool,
+
+    /// Remove keys from the agent after this many seconds
+    #[arg(long = "key-lifetime", value_name = "SECONDS")]
+    pub key_lifetime: Option<u64>,
+
+    /// Socket path (default: ssh-agent.sock next to the agent socket)
+    #[arg(long = "socket", value_name = "PATH")]
+    pub socket: Option<String>,
+
+    /// Serve in the foreground instead of starting a background process
+    #[arg(long = "foreground")]
+    pub foreground: bool,
+
+    /// Stop the running SSH agent
+    #[arg(long = "stop")]
+    pub stop: bool,
+
+    /// Hidden argument for the background agent process (internal use only)
+    #[arg(long, hide = true)]
+    pub serve_stdin: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GenerateArgs {
+    #[command(flatten)]
+    pub generator: GeneratorArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GeneratorArgs {
+    /// Generator profile from the config file
+    #[arg(long = "profile")]
+    pub profile: Option<String>,
+
+    /// Password length
+    #[arg(short = 'l', long = "length")]
+    pub length: Option<usize>,
+
+    /// Do not use lowercase letters
+    #[arg(long = "no-lower")]
+    pub no_lower: bool,
+
+    /// Do not use uppercase letters
+    #[arg(long = "no-upper")]
+    pub no_upper: bool,
+
+    /// Do not use digits
+    #[arg(long = "no-digits")]
+    pub no_digits: bool,
+
+    /// Do not use symbols
+    #[arg(long = "no-symbols")]
+    pub no_symbols: bool,
+
+    /// Exclude look-alike characters (0 O 1 I l |)
+    #[arg(long = "exclude-similar")]
+    pub exclude_similar: bool,
+
+    /// Minimum number of characters from each enabled class
+    #[arg(long = "min-per-class")]
+    pub min_per_class: Option<usize>,
+
+    /// Generate a passphrase from the bundled word list
+    #[arg(long = "passphrase")]
+    pub passphrase: bool,
+
+    /// Number of words in a passphrase
+    #[arg(long = "words")]
+    pub words: Option<usize>,
+
+    /// Separator between passphrase words
+    #[arg(long = "separator")]
+    pub separator: Option<String>,
 }
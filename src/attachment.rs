@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Result};
+use keepass::db::{Attachment, Entry, Value};
+use keepass::Database;
+use serde::Serialize;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::edit::modify_entry;
+use crate::output::{print_structured, OutputFormat};
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AttachmentInfo {
+    pub name: String,
+    pub size: usize,
+}
+
+/// Name and size of every attachment of `entry`, sorted by name.
+pub fn list(entry: &Entry) -> Vec<AttachmentInfo> {
+    let mut items: Vec<AttachmentInfo> = entry
+        .attachments
+        .iter()
+        .map(|(name, file)| AttachmentInfo {
+            name: name.clone(),
+            size: file.len(),
+        })
+        .collect();
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    items
+}
+
+/// Returns the content of the attachment called `name`.
+pub fn find<'e>(entry: &'e Entry, name: &str) -> Result<&'e [u8]> {
+    match entry.attachments.get(name) {
+        Some(file) => Ok(file.get()),
+        None => Err(not_found(entry, name)),
+    }
+}
+
+fn not_found(entry: &Entry, name: &str) -> anyhow::Error {
+    let names: Vec<String> = list(entry).into_iter().map(|i| i.name).collect();
+    if names.is_empty() {
+        anyhow!("Entry has no attachments")
+    } else {
+        anyhow!(
+            "Attachment not found: {} (available: {})",
+            name,
+            names.join(", ")
+        )
+    }
+}
+
+/// Attachment name for a local file: its file name.
+pub fn file_name(path: &str) -> Result<String> {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| anyhow!("Cannot derive an attachment name from {}", path))
+}
+
+/// Attaches `data` as `name` to the entry with `uuid`, replacing an
+/// attachment of the same name. Returns whether one was replaced.
+pub fn attach(db: &mut Database, uuid: &Uuid, name: &str, data: Vec<u8>) -> Result<bool> {
+    let mut replaced = false;
+    modify_entry(db, uuid, |entry| {
+        let file = Attachment {
+            data: Value::protected(data),
+        };
+        replaced = entry.attachments.insert(name.to_string(), file).is_some();
+        Ok(())
+    })?;
+    Ok(replaced)
+}
+
+/// Removes the attachment `name` from the entry with `uuid`.
+pub fn detach(db: &mut Database, uuid: &Uuid, name: &str) -> Result<()> {
+    modify_entry(db, uuid, |entry| match entry.attachments.remove(name) {
+        Some(_) => Ok(()),
+        None => Err(not_found(entry, name)),
+    })
+    .map(|_| ())
+}
+
+/// Prints name and size of every attachment.
+pub fn print_list(items: &[AttachmentInfo], format: &OutputFormat) -> Result<()> {
+    if !matches!(format, OutputFormat::Text) {
+        return print_structured(&items, format);
+    }
+    let width = items
+        .iter()
+        .map(|i| i.size.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for item in items {
+        println!("{:>width$}  {}", item.size, item.name, width = width);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_helper::{open_database, save_database, Credentials};
+    use crate::edit::{create_entry, FieldChanges};
+    use keepass::config::{DatabaseConfig, KdfConfig};
+
+    #[test]
+    fn test_attach_save_and_extract() {
+        let mut db = Database::new(DatabaseConfig {
+            kdf_config: KdfConfig::Aes { rounds: 10 },
+            ..Default::default()
+        });
+        create_entry(&mut db, "/Certificates/client", &FieldChanges::default()).unwrap();
+        let uuid = db.root.groups[0].entries[0].uuid;
+        let pem = b"-----BEGIN CERTIFICATE-----\nMIIB\n".to_vec();
+        assert!(!attach(&mut db, &uuid, "client.pem", pem.clone()).unwrap());
+        attach(&mut db, &uuid, "client.key", vec![0, 1, 2, 255]).unwrap();
+        assert!(attach(&mut db, &uuid, "client.key", vec![0, 1, 2]).unwrap());
+
+        let path = std::env::temp_dir()
+            .join(format!("kpasscli-test-{}-attach.kdbx", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let credentials = Credentials {
+            password: Some("secret".to_string()),
+            keyfile: None,
+        };
+        save_database(&db, &path, &credentials).unwrap();
+        let mut db = open_database(&path, &credentials).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let entry = db.root.entry_by_uuid(uuid).unwrap();
+        assert_eq!(
+            list(entry),
+            vec![
+                AttachmentInfo {
+                    name: "client.key".to_string(),
+                    size: 3,
+                },
+                AttachmentInfo {
+                    name: "client.pem".to_string(),
+                    size: pem.len(),
+                },
+            ]
+        );
+        assert_eq!(find(entry, "client.pem").unwrap(), pem.as_slice());
+        assert_eq!(find(entry, "client.key").unwrap(), &[0, 1, 2]);
+        // Every change keeps the previous version
+        assert_eq!(entry.history.as_ref().unwrap().get_entries().len(), 3);
+        assert!(find(entry, "missing")
+            .unwrap_err()
+            .to_string()
+            .contains("client.key, client.pem"));
+
+        detach(&mut db, &uuid, "client.key").unwrap();
+        assert!(detach(&mut db, &uuid, "client.key").is_err());
+        let entry = db.root.entry_by_uuid(uuid).unwrap();
+        assert_eq!(list(entry).len(), 1);
+        assert_eq!(file_name("certs/client.pem").unwrap(), "client.pem");
+    }
+}
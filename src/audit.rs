@@ -0,0 +1,253 @@
+//! Password audit: reused, weak, empty and old passwords. Reports name
+//! entries only; passwords and their hashes are never printed.
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use keepass::db::{Entry, Times};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::history;
+use crate::output::{print_structured, OutputFormat};
+use crate::search::Finder;
+
+/// Estimated entropy below which a password is weak, unless configured.
+pub const DEFAULT_MIN_ENTROPY: f64 = 60.0;
+/// Age after which an unchanged password is reported, unless configured.
+pub const DEFAULT_MAX_AGE_DAYS: u32 = 365;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Issue {
+    Reused,
+    Weak,
+    Empty,
+    Old,
+}
+
+impl Issue {
+    fn name(self) -> &'static str {
+        match self {
+            Issue::Reused => "reused",
+            Issue::Weak => "weak",
+            Issue::Empty => "empty",
+            Issue::Old => "old",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub issue: Issue,
+    pub path: String,
+    pub uuid: String,
+    /// Entries sharing a password have the same group number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entropy_bits: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_days: Option<i64>,
+}
+
+impl Finding {
+    fn new(issue: Issue, path: &str, entry: &Entry) -> Self {
+        Self {
+            issue,
+            path: path.to_string(),
+            uuid: entry.uuid.to_string(),
+            group: None,
+            entropy_bits: None,
+            age_days: None,
+        }
+    }
+}
+
+pub struct Thresholds {
+    pub min_entropy: f64,
+    pub max_age_days: u32,
+}
+
+/// Audits every entry outside the Recycle Bin. Findings are ordered by
+/// issue, reused passwords by group.
+pub fn audit(finder: &Finder, thresholds: &Thresholds) -> Vec<Finding> {
+    let now = Times::now();
+    let mut findings = Vec::new();
+    let mut by_hash: HashMap<[u8; 32], Vec<(String, String)>> = HashMap::new();
+
+    finder.for_each_entry(&mut |path, entry| {
+        let password = entry.get_password().unwrap_or_default();
+        if password.is_empty() {
+            findings.push(Finding::new(Issue::Empty, path, entry));
+            return;
+        }
+        by_hash
+            .entry(Sha256::digest(password.as_bytes()).into())
+            .or_default()
+            .push((path.to_string(), entry.uuid.to_string()));
+
+        let bits = entropy_bits(password);
+        if bits < thresholds.min_entropy {
+            findings.push(Finding {
+                entropy_bits: Some(bits as u32),
+                ..Finding::new(Issue::Weak, path, entry)
+            });
+        }
+        if let Some(changed) = password_changed(entry) {
+            let age = (now - changed).num_days();
+            if age > thresholds.max_age_days.into() {
+                findings.push(Finding {
+                    age_days: Some(age),
+                    ..Finding::new(Issue::Old, path, entry)
+                });
+            }
+        }
+    });
+
+    let mut reused: Vec<Vec<(String, String)>> = by_hash
+        .into_values()
+        .filter(|entries| entries.len() > 1)
+        .collect();
+    reused.sort();
+    for (index, entries) in reused.into_iter().enumerate() {
+        for (path, uuid) in entries {
+            findings.push(Finding {
+                issue: Issue::Reused,
+                path,
+                uuid,
+                group: Some(index + 1),
+                entropy_bits: None,
+                age_days: None,
+            });
+        }
+    }
+
+    let order = [Issue::Reused, Issue::Weak, Issue::Empty, Issue::Old];
+    findings.sort_by_key(|f| order.iter().position(|i| *i == f.issue));
+    findings
+}
+
+/// Rough entropy estimate: length times the bits per character of the
+/// character classes used.
+pub fn entropy_bits(password: &str) -> f64 {
+    let has = |f: fn(&char) -> bool| password.chars().any(|c| f(&c));
+    let mut pool = 0;
+    if has(char::is_ascii_lowercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_uppercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_digit) {
+        pool += 10;
+    }
+    if has(|c| c.is_ascii() && !c.is_ascii_alphanumeric()) {
+        pool += 33;
+    }
+    if has(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    password.chars().count() as f64 * f64::from(pool).log2()
+}
+
+/// When the current password was set: the modification time of the oldest
+/// consecutive version with the same password, else the creation time.
+fn password_changed(entry: &Entry) -> Option<NaiveDateTime> {
+    let password = entry.get_password();
+    let mut changed = None;
+    for version in history::versions(entry) {
+        if version.get_password() != password {
+            break;
+        }
+        changed = version
+            .times
+            .last_modification
+            .as_ref()
+            .or(version.times.creation.as_ref())
+            .copied()
+            .or(changed);
+    }
+    changed
+}
+
+pub fn print_report(findings: &[Finding], format: &OutputFormat) -> Result<()> {
+    if !matches!(format, OutputFormat::Text) {
+        return print_structured(&findings, format);
+    }
+    if findings.is_empty() {
+        println!("No issues found");
+        return Ok(());
+    }
+    println!("{:7} {:40} DETAIL", "ISSUE", "PATH");
+    for finding in findings {
+        let detail = match finding.issue {
+            Issue::Reused => format!("reuse group {}", finding.group.unwrap_or(0)),
+            Issue::Weak => format!("~{} bits", finding.entropy_bits.unwrap_or(0)),
+            Issue::Empty => String::new(),
+            Issue::Old => format!("unchanged for {} days", finding.age_days.unwrap_or(0)),
+        };
+        let line = format!("{:7} {:40} {}", finding.issue.name(), finding.path, detail);
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use keepass::db::Value;
+    use keepass::Database;
+
+    use crate::search::SearchOptions;
+
+    fn entry(title: &str, password: &str, modified_days_ago: i64) -> Entry {
+        let mut entry = Entry::new();
+        for (name, value) in [("Title", title), ("Password", password)] {
+            entry
+                .fields
+                .insert(name.to_string(), Value::Unprotected(value.to_string()));
+        }
+        entry.times.last_modification = Some(Times::now() - Duration::days(modified_days_ago));
+        entry
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert!(entropy_bits("password") < 40.0);
+        assert!(entropy_bits("xK9#mQ2$vL7!pR4@wN8%") > 120.0);
+    }
+
+    #[test]
+    fn test_audit() {
+        let strong = "xK9#mQ2$vL7!pR4@wN8%";
+        let mut db = Database::new(Default::default());
+        db.root.entries.push(entry("a", strong, 1));
+        db.root.entries.push(entry("b", strong, 400));
+        db.root.entries.push(entry("c", "", 1));
+        db.root.entries.push(entry("d", "secret", 1));
+
+        let finder = Finder::new(&db, SearchOptions::default());
+        let thresholds = Thresholds {
+            min_entropy: DEFAULT_MIN_ENTROPY,
+            max_age_days: DEFAULT_MAX_AGE_DAYS,
+        };
+        let findings = audit(&finder, &thresholds);
+        let summary: Vec<(Issue, &str)> = findings
+            .iter()
+            .map(|f| (f.issue, f.path.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Issue::Reused, "/a"),
+                (Issue::Reused, "/b"),
+                (Issue::Weak, "/d"),
+                (Issue::Empty, "/c"),
+                (Issue::Old, "/b"),
+            ]
+        );
+        assert_eq!(findings[0].group, Some(1));
+    }
+}
@@ -1,7 +1,10 @@
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use anyhow::{Context, Result};
+
+use crate::generator::GeneratorProfile;
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Config {
@@ -13,8 +16,30 @@ pub struct Config {
     pub password_file: Option<String>,
     #[serde(rename = "password_executable")]
     pub password_executable: Option<String>,
+    #[serde(rename = "keyfile_path", default)]
+    pub keyfile_path: Option<String>,
     #[serde(default)]
     pub clipboard_timeout: Option<u64>,
+    #[serde(default)]
+    pub agent_idle_timeout: Option<u64>,
+    #[serde(default)]
+    pub agent_lifetime: Option<u64>,
+    #[serde(default)]
+    pub git_credential_group: Option<String>,
+    #[serde(default)]
+    pub docker_registry_group: Option<String>,
+    #[serde(default)]
+    pub ssh_agent_group: Option<String>,
+    #[serde(default)]
+    pub ssh_agent_tag: Option<String>,
+    #[serde(default)]
+    pub audit_min_entropy: Option<f64>,
+    #[serde(default)]
+    pub audit_max_age_days: Option<u32>,
+    #[serde(default)]
+    pub hibp_path: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub generator_profiles: HashMap<String, GeneratorProfile>,
     #[serde(skip)]
     pub config_file_path: String,
 }
@@ -22,7 +47,7 @@ pub struct Config {
 impl Config {
     pub fn load(config_path: &str) -> Result<Self> {
         let path = resolve_config_path(config_path);
-        
+
         if !path.exists() {
             return Ok(Config {
                 config_file_path: path.to_string_lossy().to_string(),
@@ -33,9 +58,9 @@ impl Config {
         let content = fs::read_to_string(&path)
             .with_context(|| format!("Failed to read config file: {:?}", path))?;
 
-        let mut config: Config = serde_yaml::from_str(&content)
-            .with_context(|| "Failed to parse config file")?;
-        
+        let mut config: Config =
+            serde_yaml::from_str(&content).with_context(|| "Failed to parse config file")?;
+
         config.config_file_path = path.to_string_lossy().to_string();
         Ok(config)
     }
@@ -46,7 +71,26 @@ impl Config {
             default_output: Some("stdout".to_string()),
             password_file: Some("/path/to/your/password.txt".to_string()),
             password_executable: Some("[/path/to/your/]password_executable.sh".to_string()),
+            keyfile_path: Some("/path/to/your/database.keyx".to_string()),
             clipboard_timeout: Some(15),
+            agent_idle_timeout: Some(900),
+            agent_lifetime: Some(28800),
+            git_credential_group: Some("/Git".to_string()),
+            docker_registry_group: Some("/Registries".to_string()),
+            ssh_agent_group: Some("/SSH".to_string()),
+            ssh_agent_tag: None,
+            audit_min_entropy: Some(60.0),
+            audit_max_age_days: Some(365),
+            hibp_path: Some("/path/to/pwnedpasswords.txt".to_string()),
+            generator_profiles: HashMap::from([(
+                "strict".to_string(),
+                GeneratorProfile {
+                    length: 32,
+                    exclude_similar: true,
+                    min_per_class: 2,
+                    ..Default::default()
+                },
+            )]),
             config_file_path: "".to_string(),
         };
 
@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Context, Result};
+use keepass::error::{DatabaseKeyError, DatabaseOpenError};
 use keepass::{Database, DatabaseKey};
 use std::fs::File;
 
@@ -7,36 +8,167 @@ use std::process::Command;
 
 use crate::config::Config;
 
-pub fn open_database(path: &str, password: &str) -> Result<Database> {
+/// Key material for a database: a password, a key file, or both.
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub password: Option<String>,
+    pub keyfile: Option<String>,
+}
+
+impl Credentials {
+    pub fn key(&self) -> Result<DatabaseKey> {
+        let mut key = DatabaseKey::new();
+        if let Some(password) = &self.password {
+            key = key.with_password(password);
+        }
+        if let Some(keyfile) = &self.keyfile {
+            let mut file = File::open(expand_home(keyfile))
+                .with_context(|| format!("Failed to open key file: {}", keyfile))?;
+            key = key
+                .with_keyfile(&mut file)
+                .with_context(|| format!("Failed to read key file: {}", keyfile))?;
+        }
+        Ok(key)
+    }
+}
+
+pub fn open_database(path: &str, credentials: &Credentials) -> Result<Database> {
     let path = Path::new(path);
     let mut file =
         File::open(path).with_context(|| format!("Failed to open database file: {:?}", path))?;
 
-    let key = DatabaseKey::new().with_password(password);
+    let key = credentials.key()?;
     Database::open(&mut file, key)
         .with_context(|| "Failed to open KeePass database. Check password or keyfile.")
 }
 
+/// Opens the database, prompting for a password if a key file alone was
+/// tried and rejected (composite key without a configured password source).
+pub fn unlock_database(path: &str, credentials: &mut Credentials) -> Result<Database> {
+    let keyfile_only = credentials.password.is_none() && credentials.keyfile.is_some();
+    match open_database(path, credentials) {
+        Err(e) if keyfile_only && is_key_error(&e) => {
+            let password = rpassword::prompt_password("Enter password: ")
+                .context("Failed to read password")?;
+            credentials.password = Some(password);
+            open_database(path, credentials)
+        }
+        result => result,
+    }
+}
+
+/// Writes the database back to `path` atomically: the new content goes to a
+/// temporary file in the same directory which then replaces the original.
+pub fn save_database(db: &Database, path: &str, credentials: &Credentials) -> Result<()> {
+    let path = Path::new(path);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid database path: {:?}", path))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    let key = credentials.key()?;
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&tmp_path)
+            .with_context(|| format!("Failed to create temporary file: {:?}", tmp_path))?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&tmp_path, metadata.permissions())?;
+        }
+        db.save(&mut file, key)
+            .with_context(|| "Failed to write KeePass database")?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace database file: {:?}", path))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// An unlocked database together with what is needed to save it again.
+pub struct Session {
+    pub db: Database,
+    pub path: String,
+    pub credentials: Credentials,
+}
+
+impl Session {
+    pub fn save(&self) -> Result<()> {
+        save_database(&self.db, &self.path, &self.credentials)
+    }
+}
+
+fn is_key_error(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<DatabaseOpenError>(),
+        Some(DatabaseOpenError::Key(DatabaseKeyError::IncorrectKey))
+    )
+}
+
+pub fn resolve_keyfile(
+    keyfile_param: Option<String>,
+    cfg: &Config,
+    keyfile_env: Option<String>,
+) -> Option<String> {
+    keyfile_param
+        .or(keyfile_env)
+        .or_else(|| cfg.keyfile_path.clone())
+}
+
+/// Returns the database password, or `None` when a key file is configured
+/// and no password source is, so keyfile-only databases open without a prompt.
 pub fn resolve_password(
     pass_param: Option<String>,
     cfg: &Config,
     kdb_pass_env: Option<String>,
-) -> Result<String> {
+    keyfile: Option<&str>,
+) -> Result<Option<String>> {
     if let Some(p) = pass_param {
-        return resolve_password_from_source(&p);
+        return resolve_password_from_source(&p).map(Some);
     }
     if let Some(p) = kdb_pass_env {
-        return resolve_password_from_source(&p);
+        return resolve_password_from_source(&p).map(Some);
     }
     if let Some(p) = &cfg.password_file {
-        return resolve_password_from_source(p);
+        return resolve_password_from_source(p).map(Some);
     }
     if let Some(p) = &cfg.password_executable {
-        return resolve_password_from_source(p);
+        return resolve_password_from_source(p).map(Some);
+    }
+    if keyfile.is_some() {
+        return Ok(None);
     }
 
     // Prompt user
-    rpassword::prompt_password("Enter password: ").context("Failed to read password")
+    rpassword::prompt_password("Enter password: ")
+        .map(Some)
+        .context("Failed to read password")
+}
+
+/// Reads a secret for an entry field from a password file/executable, from
+/// the first line of stdin, or from an interactive prompt, in that order.
+pub fn read_secret(source: Option<&str>, from_stdin: bool, prompt: &str) -> Result<String> {
+    if let Some(source) = source {
+        return resolve_password_from_source(source);
+    }
+    if from_stdin {
+        let mut line = String::new();
+        std::io::stdin()
+            .read_line(&mut line)
+            .context("Failed to read secret from stdin")?;
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    rpassword::prompt_password(prompt).context("Failed to read password")
+}
+
+fn expand_home(path: &str) -> std::path::PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    std::path::PathBuf::from(path)
 }
 
 fn resolve_password_from_source(source: &str) -> Result<String> {
@@ -0,0 +1,147 @@
+//! Docker credential helper (`docker-credential-kpasscli`). Docker sends the
+//! registry server URL, or for `store` a JSON credential, on stdin and reads
+//! JSON answers from stdout.
+
+use anyhow::{anyhow, Result};
+use keepass::db::Group;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::search::{parse_entry_url, SearchResult, UrlQuery};
+
+/// Group for registry entries, unless configured.
+pub const DEFAULT_GROUP: &str = "/Registries";
+
+/// Docker reports this exact message as "credentials not found".
+pub const NOT_FOUND: &str = "credentials not found in native keychain";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegistryCredentials {
+    #[serde(rename = "ServerURL")]
+    pub server_url: String,
+    #[serde(rename = "Username")]
+    pub username: String,
+    #[serde(rename = "Secret")]
+    pub secret: String,
+}
+
+/// Returns `host[:port]` of a registry server URL like
+/// `https://index.docker.io/v1/` or `registry.example.com:5000`.
+pub fn registry_host(server_url: &str) -> Result<String> {
+    let url = parse_entry_url(server_url)
+        .ok_or_else(|| anyhow!("Invalid registry server URL: {}", server_url))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("Registry server URL has no host: {}", server_url))?;
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Lookup for entries whose URL field points at the registry.
+pub fn registry_query(server_url: &str) -> Result<UrlQuery> {
+    Ok(UrlQuery {
+        host: registry_host(server_url)?,
+        ..Default::default()
+    })
+}
+
+/// Path of the registry entry in `group`, titled after the registry host.
+pub fn entry_path(group: &str, server_url: &str) -> Result<String> {
+    Ok(format!(
+        "{}/{}",
+        group.trim_end_matches('/'),
+        registry_host(server_url)?
+    ))
+}
+
+/// Whether `result` is an entry directly in `group`, i.e. one that `list`
+/// reports; `get` and `erase` only touch those.
+pub fn in_group(group: &str, result: &SearchResult) -> bool {
+    let title = result.entry.get_title().unwrap_or_default();
+    result
+        .path
+        .strip_suffix(title)
+        .and_then(|parent| parent.strip_suffix('/'))
+        .is_some_and(|parent| parent.trim_matches('/') == group.trim_matches('/'))
+}
+
+/// Answers `list`: server URL to user name for every entry in the registry
+/// group. The URL field is used as server URL, the title if it is empty.
+pub fn list(group: &Group) -> BTreeMap<String, String> {
+    group
+        .entries
+        .iter()
+        .map(|entry| {
+            let server_url = entry
+                .get_url()
+                .filter(|u| !u.is_empty())
+                .or(entry.get_title())
+                .unwrap_or_default();
+            (
+                server_url.to_string(),
+                entry.get_username().unwrap_or_default().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_host() {
+        assert_eq!(
+            registry_host("https://index.docker.io/v1/").unwrap(),
+            "index.docker.io"
+        );
+        assert_eq!(
+            registry_host("registry.example.com:5000").unwrap(),
+            "registry.example.com:5000"
+        );
+        assert_eq!(
+            entry_path("/Registries/", "ghcr.io").unwrap(),
+            "/Registries/ghcr.io"
+        );
+    }
+
+    #[test]
+    fn test_in_group() {
+        use keepass::db::{Entry, Value};
+
+        let result = |path: &str, title: &str| {
+            let mut entry = Entry::new();
+            entry
+                .fields
+                .insert("Title".to_string(), Value::Unprotected(title.to_string()));
+            SearchResult {
+                path: path.to_string(),
+                entry,
+                matched: Vec::new(),
+            }
+        };
+        assert!(in_group(
+            "/Registries",
+            &result("/Registries/ghcr.io", "ghcr.io")
+        ));
+        assert!(in_group("Registries/", &result("/Registries/a/b", "a/b")));
+        assert!(!in_group(
+            "/Registries",
+            &result("/Registries/Old/ghcr.io", "ghcr.io")
+        ));
+        assert!(!in_group(
+            "/Registries",
+            &result("/Work/ghcr.io", "ghcr.io")
+        ));
+    }
+
+    #[test]
+    fn test_credentials_wire_format() {
+        let json = r#"{"ServerURL":"ghcr.io","Username":"bot","Secret":"token"}"#;
+        let credentials: RegistryCredentials = serde_json::from_str(json).unwrap();
+        assert_eq!(credentials.username, "bot");
+        assert_eq!(serde_json::to_string(&credentials).unwrap(), json);
+    }
+}
@@ -0,0 +1,525 @@
+use anyhow::{anyhow, Result};
+use keepass::db::{Entry, Group, History, Times, Value};
+use keepass::Database;
+use uuid::Uuid;
+
+use crate::args::{AddArgs, EditArgs};
+
+/// Fields every KeePass entry has; unsetting one of them clears its value.
+const STANDARD_FIELDS: [&str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
+/// KeePass keeps this many history items per entry unless configured otherwise.
+const DEFAULT_HISTORY_MAX_ITEMS: usize = 10;
+
+/// Splits an entry path like `/Servers/prod/db01` into its group names and
+/// the entry title. A leading root group name is ignored.
+pub fn split_entry_path<'p>(root: &Group, path: &'p str) -> Result<(Vec<&'p str>, &'p str)> {
+    let mut parts: Vec<&str> = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() > 1 && parts[0] == root.name {
+        parts.remove(0);
+    }
+    let title = parts
+        .pop()
+        .ok_or_else(|| anyhow!("Entry path must not be empty"))?;
+    Ok((parts, title))
+}
+
+/// Splits a group path like `/Servers/prod` into group names. A leading root
+/// group name is ignored.
+pub fn split_group_path<'p>(root: &Group, path: &'p str) -> Vec<&'p str> {
+    let mut parts: Vec<&str> = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|p| !p.is_empty())
+        .collect();
+    if !parts.is_empty() && parts[0] == root.name {
+        parts.remove(0);
+    }
+    parts
+}
+
+/// Walks down `groups` from `root`, creating every missing group on the way.
+pub fn ensure_group<'g>(root: &'g mut Group, groups: &[&str]) -> &'g mut Group {
+    let mut current = root;
+    for name in groups {
+        let index = match current.groups.iter().position(|g| g.name == *name) {
+            Some(i) => i,
+            None => {
+                current.groups.push(Group::new(name));
+                current.groups.len() - 1
+            }
+        };
+        current = &mut current.groups[index];
+    }
+    current
+}
+
+pub fn protected(value: &str) -> Value<String> {
+    Value::protected(value)
+}
+
+pub fn unprotected(value: &str) -> Value<String> {
+    Value::unprotected(value)
+}
+
+/// Parses a `NAME=VALUE` field assignment.
+pub fn parse_assignment(assignment: &str) -> Result<(&str, &str)> {
+    assignment
+        .split_once('=')
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid field assignment '{}', expected NAME=VALUE",
+                assignment
+            )
+        })
+}
+
+/// Field changes applied to an entry by `add`, `edit` and the credential
+/// helpers. Field names are matched against the standard fields ignoring case.
+#[derive(Debug, Default)]
+pub struct FieldChanges<'a> {
+    pub set: Vec<(&'a str, &'a str)>,
+    pub unset: Vec<&'a str>,
+    pub protect: Vec<&'a str>,
+    pub add_tags: Vec<&'a str>,
+    pub remove_tags: Vec<&'a str>,
+}
+
+impl FieldChanges<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+            && self.unset.is_empty()
+            && self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+    }
+}
+
+/// Creates a new entry at `args.path` and returns its normalized path.
+pub fn add_entry(db: &mut Database, args: &AddArgs, password: Option<&str>) -> Result<String> {
+    let mut changes = FieldChanges {
+        protect: args.protect.iter().map(String::as_str).collect(),
+        add_tags: args.tags.iter().map(String::as_str).collect(),
+        ..Default::default()
+    };
+    if let Some(username) = &args.username {
+        changes.set.push(("UserName", username));
+    }
+    if let Some(password) = password {
+        changes.set.push(("Password", password));
+    }
+    if let Some(url) = &args.url {
+        changes.set.push(("URL", url));
+    }
+    if let Some(notes) = &args.notes {
+        changes.set.push(("Notes", notes));
+    }
+    for assignment in &args.fields {
+        changes.set.push(parse_assignment(assignment)?);
+    }
+    create_entry(db, &args.path, &changes)
+}
+
+/// Creates a new entry at `path` with the given fields and returns its
+/// normalized path.
+pub fn create_entry(db: &mut Database, path: &str, changes: &FieldChanges) -> Result<String> {
+    let (groups, title) = split_entry_path(&db.root, path)?;
+    let group = ensure_group(&mut db.root, &groups);
+
+    if group.entry_by_name(title).is_some() {
+        return Err(anyhow!("Entry already exists: {}", path));
+    }
+
+    let mut entry = Entry::new();
+    entry.fields.insert("Title".to_string(), unprotected(title));
+    apply_changes(&mut entry, changes)?;
+    group.entries.push(entry);
+
+    let mut path = String::new();
+    for part in groups.iter().chain(std::iter::once(&title)) {
+        path.push('/');
+        path.push_str(part);
+    }
+    Ok(path)
+}
+
+/// Maps a field name to the standard KeePass key if it names one (ignoring
+/// case), mirroring the lookup in `get_field_value`.
+fn canonical_field_name(name: &str) -> &str {
+    STANDARD_FIELDS
+        .iter()
+        .find(|f| f.eq_ignore_ascii_case(name))
+        .copied()
+        .unwrap_or(name)
+}
+
+fn apply_changes(entry: &mut Entry, changes: &FieldChanges) -> Result<()> {
+    for (name, value) in &changes.set {
+        let name = canonical_field_name(name);
+        let protect = name == "Password"
+            || changes.protect.contains(&name)
+            || matches!(entry.fields.get(name), Some(Value::Protected(_)));
+        let value = if protect {
+            protected(value)
+        } else {
+            unprotected(value)
+        };
+        entry.fields.insert(name.to_string(), value);
+    }
+    for name in &changes.unset {
+        let name = canonical_field_name(name);
+        if name == "Title" {
+            return Err(anyhow!("The Title field cannot be unset"));
+        }
+        if STANDARD_FIELDS.contains(&name) {
+            entry.fields.insert(name.to_string(), unprotected(""));
+        } else if entry.fields.remove(name).is_none() {
+            return Err(anyhow!("Field '{}' not found", name));
+        }
+    }
+    for tag in &changes.add_tags {
+        let tag = tag.trim();
+        // KeePass separates tags with ';' (older versions with ',')
+        if tag.is_empty() || tag.contains([';', ',']) {
+            return Err(anyhow!("Invalid tag: '{}'", tag));
+        }
+        if !entry.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            entry.tags.push(tag.to_string());
+        }
+    }
+    for tag in &changes.remove_tags {
+        let count = entry.tags.len();
+        entry.tags.retain(|t| !t.eq_ignore_ascii_case(tag.trim()));
+        if entry.tags.len() == count {
+            return Err(anyhow!("Tag '{}' not found", tag));
+        }
+    }
+    Ok(())
+}
+
+/// Sets or unsets fields of the entry with `uuid` as requested on the
+/// command line.
+pub fn edit_entry(
+    db: &mut Database,
+    uuid: &Uuid,
+    args: &EditArgs,
+    password: Option<&str>,
+) -> Result<()> {
+    let mut changes = FieldChanges {
+        unset: args.unset.iter().map(String::as_str).collect(),
+        protect: args.protect.iter().map(String::as_str).collect(),
+        add_tags: args.tags.iter().map(String::as_str).collect(),
+        remove_tags: args.untags.iter().map(String::as_str).collect(),
+        ..Default::default()
+    };
+    for assignment in &args.set {
+        changes.set.push(parse_assignment(assignment)?);
+    }
+    if let Some(password) = password {
+        changes.set.push(("Password", password));
+    }
+    if changes.is_empty() {
+        return Err(anyhow!("No changes requested"));
+    }
+    update_entry(db, uuid, &changes).map(|_| ())
+}
+
+/// Applies `changes` to the entry with `uuid`, keeping the previous version
+/// in the entry history. Returns whether the entry changed.
+pub fn update_entry(db: &mut Database, uuid: &Uuid, changes: &FieldChanges) -> Result<bool> {
+    modify_entry(db, uuid, |entry| apply_changes(entry, changes))
+}
+
+/// Runs `change` on the entry with `uuid`, keeping the previous version in
+/// the entry history. Returns whether the entry changed.
+pub fn modify_entry<F>(db: &mut Database, uuid: &Uuid, change: F) -> Result<bool>
+where
+    F: FnOnce(&mut Entry) -> Result<()>,
+{
+    // A negative limit means the history is unlimited
+    let history_max_items = db
+        .meta
+        .history_max_items
+        .map_or(DEFAULT_HISTORY_MAX_ITEMS, |n| {
+            usize::try_from(n).unwrap_or(usize::MAX)
+        });
+
+    let entry = find_entry_mut(&mut db.root, uuid).ok_or_else(|| anyhow!("Entry not found"))?;
+    let previous = entry.clone();
+    change(entry)?;
+
+    if *entry == previous {
+        return Ok(false);
+    }
+    push_history(entry, previous, history_max_items);
+    entry.times.last_modification = Some(Times::now());
+    Ok(true)
+}
+
+/// Adds `previous` as the newest history item of `entry` and drops the
+/// oldest items beyond `max_items`. Like KeePass, the history is stored
+/// oldest first.
+fn push_history(entry: &mut Entry, mut previous: Entry, max_items: usize) {
+    previous.history = None;
+    let mut kept: Vec<Entry> = entry
+        .history
+        .take()
+        .map(|h| h.get_entries().clone())
+        .unwrap_or_default();
+    kept.push(previous);
+    let excess = kept.len().saturating_sub(max_items);
+    kept.drain(..excess);
+
+    // add_entry inserts at the front
+    let mut history = History::default();
+    for item in kept.into_iter().rev() {
+        history.add_entry(item);
+    }
+    entry.history = Some(history);
+}
+
+/// Moves the entry with `uuid` into the group at `group_path`, creating it
+/// if needed, and returns the new entry path.
+pub fn move_entry(db: &mut Database, uuid: &Uuid, group_path: &str) -> Result<String> {
+    let groups = split_group_path(&db.root, group_path);
+    let title = find_entry(&db.root, uuid)
+        .ok_or_else(|| anyhow!("Entry not found"))?
+        .get_title()
+        .unwrap_or_default()
+        .to_string();
+    // Check the destination before the entry is taken out of its group
+    let taken = db
+        .root
+        .group_by_path(&groups)
+        .and_then(|group| group.entry_by_name(&title))
+        .is_some_and(|existing| existing.uuid != *uuid);
+    if taken {
+        return Err(anyhow!("Entry already exists in {}: {}", group_path, title));
+    }
+
+    let mut entry = remove_entry(&mut db.root, uuid).ok_or_else(|| anyhow!("Entry not found"))?;
+    entry.times.location_changed = Some(Times::now());
+    ensure_group(&mut db.root, &groups).entries.push(entry);
+
+    let mut path = String::new();
+    for part in groups.iter().chain(std::iter::once(&title.as_str())) {
+        path.push('/');
+        path.push_str(part);
+    }
+    Ok(path)
+}
+
+/// Removes the entry with `uuid`. Unless `force` is set (or the entry is
+/// already there) the entry is moved to the Recycle Bin, which is created on
+/// first use. Returns whether the entry was recycled.
+pub fn remove(db: &mut Database, uuid: &Uuid, force: bool) -> Result<bool> {
+    let in_recycle_bin = recycle_bin(db).is_some_and(|bin| find_entry(bin, uuid).is_some());
+    let recycle = !force && !in_recycle_bin && db.meta.recyclebin_enabled.unwrap_or(true);
+
+    let mut entry = remove_entry(&mut db.root, uuid).ok_or_else(|| anyhow!("Entry not found"))?;
+
+    if recycle {
+        entry.times.location_changed = Some(Times::now());
+        recycle_bin_mut(db).entries.push(entry);
+    } else {
+        db.deleted_objects.insert(entry.uuid, Some(Times::now()));
+    }
+    Ok(recycle)
+}
+
+pub fn recycle_bin(db: &Database) -> Option<&Group> {
+    let uuid = db.meta.recyclebin_uuid?;
+    find_group(&db.root, &uuid)
+}
+
+fn recycle_bin_mut(db: &mut Database) -> &mut Group {
+    let existing = recycle_bin(db).map(|g| g.uuid);
+    let uuid = match existing {
+        Some(uuid) => uuid,
+        None => {
+            let mut bin = Group::new("Recycle Bin");
+            bin.icon_id = Some(43);
+            bin.enable_autotype = Some(false);
+            bin.enable_searching = Some(false);
+            let uuid = bin.uuid;
+            db.root.groups.push(bin);
+            db.meta.recyclebin_enabled = Some(true);
+            db.meta.recyclebin_uuid = Some(uuid);
+            db.meta.recyclebin_changed = Some(Times::now());
+            uuid
+        }
+    };
+    find_group_mut(&mut db.root, &uuid).expect("recycle bin exists")
+}
+
+fn find_group<'g>(group: &'g Group, uuid: &Uuid) -> Option<&'g Group> {
+    group.group_by_uuid(*uuid)
+}
+
+fn find_group_mut<'g>(group: &'g mut Group, uuid: &Uuid) -> Option<&'g mut Group> {
+    group.group_by_uuid_mut(*uuid)
+}
+
+fn find_entry<'g>(group: &'g Group, uuid: &Uuid) -> Option<&'g Entry> {
+    group.entry_by_uuid(*uuid)
+}
+
+pub fn find_entry_mut<'g>(group: &'g mut Group, uuid: &Uuid) -> Option<&'g mut Entry> {
+    group.entry_by_uuid_mut(*uuid)
+}
+
+fn remove_entry(group: &mut Group, uuid: &Uuid) -> Option<Entry> {
+    if let Some(index) = group.entries.iter().position(|e| e.uuid == *uuid) {
+        return Some(group.entries.remove(index));
+    }
+    group.groups.iter_mut().find_map(|g| remove_entry(g, uuid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{Args, Command};
+    use crate::db_helper::{open_database, save_database, Credentials};
+    use clap::Parser;
+    use keepass::config::{DatabaseConfig, KdfConfig};
+
+    fn entry<'d>(db: &'d Database, path: &[&str]) -> &'d Entry {
+        let (title, groups) = path.split_last().expect("entry path");
+        db.root
+            .group_by_path(groups)
+            .and_then(|g| g.entry_by_name(title))
+            .unwrap_or_else(|| panic!("entry {:?} not found", path))
+    }
+
+    /// Saves `db` to a temporary file and opens it again.
+    fn reopen(db: &Database, name: &str) -> Database {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "kpasscli-test-{}-{}.kdbx",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .into_owned();
+        let credentials = Credentials {
+            password: Some("secret".to_string()),
+            keyfile: None,
+        };
+        save_database(db, &path, &credentials).unwrap();
+        let reopened = open_database(&path, &credentials).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        reopened
+    }
+
+    fn database() -> Database {
+        Database::new(DatabaseConfig {
+            kdf_config: KdfConfig::Aes { rounds: 10 },
+            ..Default::default()
+        })
+    }
+
+    fn command(args: &[&str]) -> Command {
+        let args = Args::parse_from(["kpasscli"].iter().chain(args));
+        args.command.expect("subcommand")
+    }
+
+    fn add_args(args: &[&str]) -> AddArgs {
+        match command(&[&["add"], args].concat()) {
+            Command::Add(add) => add,
+            _ => unreachable!(),
+        }
+    }
+
+    fn edit_args(args: &[&str]) -> EditArgs {
+        match command(&[&["edit", "host"], args].concat()) {
+            Command::Edit(edit) => edit,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_add_entry() {
+        let mut db = database();
+        let args = add_args(&[
+            "/Servers/db01",
+            "-u",
+            "admin",
+            "--field",
+            "Port=5432",
+            "--tag",
+            "prod",
+        ]);
+        let path = add_entry(&mut db, &args, Some("pw")).unwrap();
+        assert_eq!(path, "/Servers/db01");
+        assert!(add_entry(&mut db, &add_args(&["Servers/db01"]), None).is_err());
+
+        let db = reopen(&db, "add");
+        let e = entry(&db, &["Servers", "db01"]);
+        assert_eq!(e.get_username(), Some("admin"));
+        assert!(matches!(
+            e.fields.get("Password"),
+            Some(Value::Protected(_))
+        ));
+        assert_eq!(e.get("Port"), Some("5432"));
+        assert_eq!(e.tags, vec!["prod".to_string()]);
+    }
+
+    #[test]
+    fn test_edit_entry_history() {
+        let mut db = database();
+        db.meta.history_max_items = Some(2);
+        add_entry(&mut db, &add_args(&["/host"]), None).unwrap();
+        let uuid = entry(&db, &["host"]).uuid;
+
+        for password in ["one", "two", "three", "three"] {
+            let args = edit_args(&["--set", &format!("Password={}", password)]);
+            edit_entry(&mut db, &uuid, &args, None).unwrap();
+        }
+
+        let db = reopen(&db, "history");
+        let e = entry(&db, &["host"]);
+        assert_eq!(e.get_password(), Some("three"));
+        let history: Vec<_> = e
+            .history
+            .as_ref()
+            .unwrap()
+            .get_entries()
+            .iter()
+            .map(|h| h.get_password())
+            .collect();
+        assert_eq!(history, vec![Some("one"), Some("two")]);
+    }
+
+    #[test]
+    fn test_move_and_remove_entry() {
+        let mut db = database();
+        add_entry(&mut db, &add_args(&["/old/host"]), None).unwrap();
+        let uuid = entry(&db, &["old", "host"]).uuid;
+        add_entry(&mut db, &add_args(&["/taken/host"]), None).unwrap();
+        assert!(move_entry(&mut db, &uuid, "/taken").is_err());
+        assert!(find_entry(&db.root, &uuid).is_some());
+
+        assert_eq!(
+            move_entry(&mut db, &uuid, "/new/sub").unwrap(),
+            "/new/sub/host"
+        );
+        assert!(remove(&mut db, &uuid, false).unwrap());
+
+        let mut db = reopen(&db, "move");
+        assert!(db
+            .root
+            .group_by_path(&["new", "sub"])
+            .and_then(|g| g.entry_by_name("host"))
+            .is_none());
+        assert!(recycle_bin(&db).is_some_and(|bin| find_entry(bin, &uuid).is_some()));
+
+        assert!(!remove(&mut db, &uuid, false).unwrap());
+        let db = reopen(&db, "remove");
+        assert!(find_entry(&db.root, &uuid).is_none());
+        assert!(db.deleted_objects.contains_key(&uuid));
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::process::Command;
+
+use crate::search::is_not_found;
+
+/// Fields every entry has; a `:FIELD` suffix naming one is always a field.
+const STANDARD_FIELDS: [&str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
+/// One environment variable to be filled from a database entry.
+#[derive(Debug, PartialEq)]
+pub struct EnvMapping {
+    pub name: String,
+    /// `ENTRY[:FIELD]`, see `resolve_reference`.
+    pub reference: String,
+}
+
+/// Splits `ENTRY:FIELD` at the last colon. A colon followed by a path
+/// separator belongs to the entry, so `/Web/http://host` stays intact.
+fn split_field(reference: &str) -> Option<(&str, &str)> {
+    reference
+        .rsplit_once(':')
+        .filter(|(item, field)| !item.is_empty() && !field.is_empty() && !field.contains('/'))
+}
+
+/// Resolves an entry reference `ENTRY[:FIELD]` (default field `Password`).
+///
+/// `read(item, field)` looks up an entry and returns the field value, or
+/// `None` if the entry has no such field. A suffix after the last colon is
+/// only a field when it names a standard field or the entry has it;
+/// otherwise the whole reference is the entry, so a title like `db:5432`
+/// keeps working. Errors other than a missing entry are passed on.
+pub fn resolve_reference<F>(reference: &str, mut read: F) -> Result<String>
+where
+    F: FnMut(&str, &str) -> Result<Option<String>>,
+{
+    let mut missing_field = None;
+    if let Some((item, field)) = split_field(reference) {
+        let standard = STANDARD_FIELDS
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(field));
+        match read(item, field) {
+            Ok(Some(value)) => return Ok(value),
+            Ok(None) if standard => return Err(anyhow!("Field '{}' not found", field)),
+            Ok(None) => missing_field = Some(field),
+            // Only a missing entry means the colon may be part of the title
+            Err(e) if standard || !is_not_found(&e) => return Err(e),
+            Err(_) => {}
+        }
+    }
+    match read(reference, "Password") {
+        Ok(value) => Ok(value.unwrap_or_default()),
+        // The entry exists, the field was probably misspelt
+        Err(e) if missing_field.is_some() && is_not_found(&e) => Err(anyhow!(
+            "Field '{}' not found",
+            missing_field.unwrap_or_default()
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses `NAME=ENTRY[:FIELD]`.
+pub fn parse_mapping(spec: &str) -> Result<EnvMapping> {
+    let (name, reference) = spec
+        .split_once('=')
+        .filter(|(name, reference)| !name.trim().is_empty() && !reference.trim().is_empty())
+        .ok_or_else(|| anyhow!("Invalid mapping '{}', expected NAME=ENTRY[:FIELD]", spec))?;
+    Ok(EnvMapping {
+        name: name.trim().to_string(),
+        reference: reference.trim().to_string(),
+    })
+}
+
+/// Reads mappings from a file, one per line; blank lines and `#` comments
+/// are ignored.
+pub fn load_env_file(path: &str) -> Result<Vec<EnvMapping>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read env file: {}", path))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(number, line)| {
+            parse_mapping(line.trim()).with_context(|| format!("{}:{}", path, number + 1))
+        })
+        .collect()
+}
+
+/// Runs `command` with `vars` added to the environment and returns the exit
+/// code to pass on (128 + signal number if the child was killed).
+pub fn run_child(command: &[String], vars: &[(String, String)]) -> Result<i32> {
+    let (program, arguments) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("No command given"))?;
+    let status = Command::new(program)
+        .args(arguments)
+        .envs(vars.iter().map(|(k, v)| (k, v)))
+        .status()
+        .with_context(|| format!("Failed to run command: {}", program))?;
+
+    if let Some(code) = status.code() {
+        return Ok(code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return Ok(128 + signal);
+        }
+    }
+    Ok(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::NotFound;
+
+    #[test]
+    fn test_parse_mapping() {
+        let mapping = parse_mapping("DB_USER=/Databases/prod:UserName").unwrap();
+        assert_eq!(
+            mapping,
+            EnvMapping {
+                name: "DB_USER".to_string(),
+                reference: "/Databases/prod:UserName".to_string(),
+            }
+        );
+        assert!(parse_mapping("DB_PASS").is_err());
+    }
+
+    /// Entries `/Databases/prod` (with a `Token` field), `/DB/db:5432` and
+    /// `/Web/http://host`; a search for `/DB/db` also finds `db:5432`.
+    fn read(item: &str, field: &str) -> Result<Option<String>> {
+        let fields: &[&str] = match item {
+            "/Databases/prod" => &["Password", "UserName", "Token"],
+            "/DB/db" | "/DB/db:5432" | "/Web/http://host" => &["Password", "UserName"],
+            "/Dup" => return Err(anyhow!("multiple items found")),
+            _ => return Err(NotFound("Entry not found: missing".to_string()).into()),
+        };
+        Ok(fields
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(field))
+            .then(|| format!("{}|{}", item, field)))
+    }
+
+    #[test]
+    fn test_resolve_reference() {
+        let resolve = |reference| resolve_reference(reference, read).unwrap();
+        assert_eq!(resolve("/Databases/prod"), "/Databases/prod|Password");
+        assert_eq!(resolve("/Databases/prod:Token"), "/Databases/prod|Token");
+        assert_eq!(
+            resolve("/Databases/prod:username"),
+            "/Databases/prod|username"
+        );
+        assert_eq!(resolve("/DB/db:5432"), "/DB/db:5432|Password");
+        assert_eq!(resolve("/Web/http://host"), "/Web/http://host|Password");
+
+        let err = resolve_reference("/Databases/prod:Tokn", read).unwrap_err();
+        assert_eq!(err.to_string(), "Field 'Tokn' not found");
+        assert!(resolve_reference("/missing:URL", read).is_err());
+        let err = resolve_reference("/Dup:Token", read).unwrap_err();
+        assert_eq!(err.to_string(), "multiple items found");
+    }
+}
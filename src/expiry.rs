@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDateTime};
+use keepass::db::{Entry, Times};
+use serde::Serialize;
+
+use crate::output::{print_structured, OutputFormat, TIME_FORMAT};
+use crate::search::{Finder, SearchResult};
+
+/// Look-ahead of the `expiring` report, unless given.
+pub const DEFAULT_DAYS: u32 = 30;
+
+/// Exit codes of the `expiring` report, usable as monitoring states.
+pub const EXIT_EXPIRING: i32 = 1;
+pub const EXIT_EXPIRED: i32 = 2;
+
+#[derive(Debug, Serialize)]
+pub struct ExpiryInfo {
+    pub path: String,
+    pub uuid: String,
+    pub expiry: String,
+    pub expired: bool,
+    /// Whole days until the expiry time, negative once expired.
+    pub days_left: i64,
+}
+
+/// The expiry time of `entry` if it is set to expire.
+pub fn expiry(entry: &Entry) -> Option<NaiveDateTime> {
+    if entry.times.expires != Some(true) {
+        return None;
+    }
+    entry.times.expiry.as_ref().copied()
+}
+
+pub fn is_expired(entry: &Entry) -> bool {
+    expiry(entry).is_some_and(|t| t <= Times::now())
+}
+
+/// Entries that expired or expire within `days`, soonest first.
+pub fn report(finder: &Finder, group: &str, days: u32) -> Vec<ExpiryInfo> {
+    let now = Times::now();
+    let limit = now + Duration::days(days.into());
+    let prefix = match group.trim_matches('/') {
+        "" => "/".to_string(),
+        group => format!("/{}/", group),
+    };
+
+    let mut items = Vec::new();
+    finder.for_each_entry(&mut |path, entry| {
+        if !path.starts_with(&prefix) {
+            return;
+        }
+        if let Some(expiry) = expiry(entry).filter(|t| *t <= limit) {
+            items.push((
+                expiry,
+                ExpiryInfo {
+                    path: path.to_string(),
+                    uuid: entry.uuid.to_string(),
+                    expiry: expiry.format(TIME_FORMAT).to_string(),
+                    expired: expiry <= now,
+                    days_left: (expiry - now).num_days(),
+                },
+            ));
+        }
+    });
+    items.sort_by_key(|(expiry, _)| *expiry);
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+pub fn print_report(items: &[ExpiryInfo], format: &OutputFormat) -> Result<()> {
+    if !matches!(format, OutputFormat::Text) {
+        return print_structured(&items, format);
+    }
+    for item in items {
+        let (status, when) = if item.expired {
+            ("EXPIRED", format!("{} days ago", -item.days_left))
+        } else {
+            ("EXPIRES", format!("in {} days", item.days_left))
+        };
+        println!("{:8} {:19}  {:14} {}", status, item.expiry, when, item.path);
+    }
+    Ok(())
+}
+
+/// Exit code for the report: expired entries take precedence.
+pub fn exit_code(items: &[ExpiryInfo]) -> i32 {
+    if items.iter().any(|item| item.expired) {
+        EXIT_EXPIRED
+    } else if !items.is_empty() {
+        EXIT_EXPIRING
+    } else {
+        0
+    }
+}
+
+/// Warns about an expired lookup result, or refuses it when `strict`.
+pub fn check(result: &SearchResult, strict: bool) -> Result<()> {
+    if !is_expired(&result.entry) {
+        return Ok(());
+    }
+    let expiry = expiry(&result.entry)
+        .map(|t| t.format(TIME_FORMAT).to_string())
+        .unwrap_or_default();
+    if strict {
+        return Err(anyhow!("Entry {} expired on {}", result.path, expiry));
+    }
+    eprintln!("Warning: entry {} expired on {}", result.path, expiry);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepass::Database;
+
+    use crate::search::SearchOptions;
+
+    fn entry(title: &str, expiry: Option<NaiveDateTime>) -> Entry {
+        let mut entry = Entry::new();
+        entry.fields.insert(
+            "Title".to_string(),
+            keepass::db::Value::Unprotected(title.to_string()),
+        );
+        if let Some(expiry) = expiry {
+            entry.times.expires = Some(true);
+            entry.times.expiry = Some(expiry);
+        }
+        entry
+    }
+
+    #[test]
+    fn test_report() {
+        let now = Times::now();
+        let mut db = Database::new(Default::default());
+        db.root
+            .entries
+            .push(entry("later", Some(now + Duration::days(90))));
+        db.root
+            .entries
+            .push(entry("soon", Some(now + Duration::days(10))));
+        db.root.entries.push(entry("never", None));
+        db.root
+            .entries
+            .push(entry("expired", Some(now - Duration::days(3))));
+
+        let finder = Finder::new(&db, SearchOptions::default());
+        let items = report(&finder, "/", 30);
+        let paths: Vec<&str> = items.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, vec!["/expired", "/soon"]);
+        assert!(items[0].expired);
+        assert_eq!(items[0].days_left, -3);
+        assert_eq!(exit_code(&items), EXIT_EXPIRED);
+        assert_eq!(exit_code(&items[1..]), EXIT_EXPIRING);
+        assert_eq!(exit_code(&report(&finder, "/", 1)[1..]), 0);
+    }
+}
@@ -0,0 +1,91 @@
+//! Relevance scoring for search results: exact matches rank above prefixes,
+//! prefixes above matches at a word boundary, those above other substrings
+//! and those above fuzzy matches (the characters of the text in order).
+
+const EXACT: i32 = 1000;
+const PREFIX: i32 = 800;
+const WORD_BOUNDARY: i32 = 600;
+const SUBSTRING: i32 = 400;
+const FUZZY: i32 = 100;
+
+/// Points a match loses per group level of its path.
+pub const DEPTH_PENALTY: i32 = 5;
+
+/// Scores how well `text` matches `value`, higher is better; `None` if the
+/// characters of `text` do not all appear in `value` in order.
+pub fn score(value: &str, text: &str, case_sensitive: bool) -> Option<i32> {
+    let (value, text) = if case_sensitive {
+        (value.to_string(), text.to_string())
+    } else {
+        (value.to_lowercase(), text.to_lowercase())
+    };
+    let value: Vec<char> = value.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    if text.is_empty() {
+        return Some(0);
+    }
+    // Shorter values are closer matches
+    let extra = value.len().saturating_sub(text.len()).min(100) as i32;
+
+    if value == text {
+        return Some(EXACT);
+    }
+    if value.starts_with(&text) {
+        return Some(PREFIX - extra);
+    }
+    let positions: Vec<usize> = value
+        .windows(text.len())
+        .enumerate()
+        .filter(|(_, window)| *window == text.as_slice())
+        .map(|(i, _)| i)
+        .collect();
+    if positions.iter().any(|&i| is_boundary(&value, i)) {
+        return Some(WORD_BOUNDARY - extra);
+    }
+    if let Some(&first) = positions.first() {
+        return Some(SUBSTRING - extra - first.min(100) as i32);
+    }
+
+    // Characters in order: reward runs and word starts, punish gaps.
+    // Spaces in the text only separate parts ("gl run").
+    let mut score = FUZZY;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for c in text.iter().filter(|c| !c.is_whitespace()) {
+        let index = next + value[next..].iter().position(|v| v == c)?;
+        if previous.is_some_and(|p| p + 1 == index) {
+            score += 5;
+        } else if is_boundary(&value, index) {
+            score += 10;
+        } else {
+            score -= (index - previous.map_or(0, |p| p + 1)).min(10) as i32;
+        }
+        previous = Some(index);
+        next = index + 1;
+    }
+    Some(score.clamp(1, SUBSTRING - 1))
+}
+
+/// Whether `index` starts a word: the first character or one following a
+/// separator like space, `-`, `_`, `.` or `/`.
+fn is_boundary(value: &[char], index: usize) -> bool {
+    index == 0 || !value[index - 1].is_alphanumeric()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_order() {
+        let score = |value: &str, text: &str| score(value, text, false);
+        assert!(score("gitlab", "GitLab") > score("gitlab-ci", "gitlab"));
+        assert!(score("gitlab-ci", "gitlab") > score("old gitlab", "gitlab"));
+        assert!(score("old gitlab", "gitlab") > score("mygitlab", "gitlab"));
+        assert!(score("mygitlab", "gitlab") > score("git-lab", "gitlab"));
+        assert!(score("prod-db-server", "pds") > score("superdays", "pds"));
+        assert!(score("gitlab-runner", "gl run").is_some());
+        assert_eq!(score("gitlab", "hub"), None);
+        assert_eq!(super::score("GitLab", "gitlab", true), None);
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Result};
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::args::GeneratorArgs;
+use crate::config::Config;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+const LOOK_ALIKES: &str = "0O1Il|";
+
+/// Bundled word list for passphrases (the 2048-word BIP-39 English list).
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+/// Password policy, either built in or configured as a named profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorProfile {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub exclude_similar: bool,
+    pub min_per_class: usize,
+    pub passphrase: bool,
+    pub words: usize,
+    pub separator: String,
+}
+
+impl Default for GeneratorProfile {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude_similar: false,
+            min_per_class: 1,
+            passphrase: false,
+            words: 6,
+            separator: "-".to_string(),
+        }
+    }
+}
+
+impl GeneratorProfile {
+    /// Starts from the named profile in `cfg` (or the defaults) and applies
+    /// the overrides given on the command line.
+    pub fn resolve(gen_args: &GeneratorArgs, cfg: &Config) -> Result<Self> {
+        let mut profile = match &gen_args.profile {
+            Some(name) => cfg
+                .generator_profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow!("Generator profile '{}' not found", name))?,
+            None => GeneratorProfile::default(),
+        };
+
+        if let Some(length) = gen_args.length {
+            profile.length = length;
+        }
+        if gen_args.no_lower {
+            profile.lowercase = false;
+        }
+        if gen_args.no_upper {
+            profile.uppercase = false;
+        }
+        if gen_args.no_digits {
+            profile.digits = false;
+        }
+        if gen_args.no_symbols {
+            profile.symbols = false;
+        }
+        if gen_args.exclude_similar {
+            profile.exclude_similar = true;
+        }
+        if let Some(min) = gen_args.min_per_class {
+            profile.min_per_class = min;
+        }
+        if gen_args.passphrase {
+            profile.passphrase = true;
+        }
+        if let Some(words) = gen_args.words {
+            profile.words = words;
+        }
+        if let Some(separator) = &gen_args.separator {
+            profile.separator = separator.clone();
+        }
+        Ok(profile)
+    }
+}
+
+pub fn generate(profile: &GeneratorProfile) -> Result<String> {
+    if profile.passphrase {
+        generate_passphrase(profile)
+    } else {
+        generate_password(profile)
+    }
+}
+
+fn generate_password(profile: &GeneratorProfile) -> Result<String> {
+    let classes: Vec<Vec<char>> = [
+        (profile.lowercase, LOWERCASE),
+        (profile.uppercase, UPPERCASE),
+        (profile.digits, DIGITS),
+        (profile.symbols, SYMBOLS),
+    ]
+    .into_iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, chars)| {
+        chars
+            .chars()
+            .filter(|c| !(profile.exclude_similar && LOOK_ALIKES.contains(*c)))
+            .collect()
+    })
+    .collect();
+
+    if classes.is_empty() {
+        return Err(anyhow!("At least one character class must be enabled"));
+    }
+    if profile.length == 0 {
+        return Err(anyhow!("Password length must be greater than zero"));
+    }
+    if profile.min_per_class * classes.len() > profile.length {
+        return Err(anyhow!(
+            "Length {} is too short for {} characters from each of {} classes",
+            profile.length,
+            profile.min_per_class,
+            classes.len()
+        ));
+    }
+
+    let mut rng = OsRng;
+    let mut password: Vec<char> = Vec::with_capacity(profile.length);
+    for class in &classes {
+        for _ in 0..profile.min_per_class {
+            password.push(class[rng.gen_range(0..class.len())]);
+        }
+    }
+
+    let all: Vec<char> = classes.concat();
+    while password.len() < profile.length {
+        password.push(all[rng.gen_range(0..all.len())]);
+    }
+    password.shuffle(&mut rng);
+
+    Ok(password.into_iter().collect())
+}
+
+fn generate_passphrase(profile: &GeneratorProfile) -> Result<String> {
+    if profile.words == 0 {
+        return Err(anyhow!("Passphrase must contain at least one word"));
+    }
+    let words: Vec<&str> = WORDLIST.lines().filter(|w| !w.is_empty()).collect();
+
+    let mut rng = OsRng;
+    let chosen: Vec<&str> = (0..profile.words)
+        .map(|_| words[rng.gen_range(0..words.len())])
+        .collect();
+    Ok(chosen.join(&profile.separator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_honours_length_and_classes() {
+        let profile = GeneratorProfile {
+            length: 12,
+            symbols: false,
+            min_per_class: 2,
+            ..Default::default()
+        };
+        let password = generate(&profile).unwrap();
+        assert_eq!(password.chars().count(), 12);
+        assert!(password.chars().filter(char::is_ascii_lowercase).count() >= 2);
+        assert!(password.chars().filter(char::is_ascii_uppercase).count() >= 2);
+        assert!(password.chars().filter(char::is_ascii_digit).count() >= 2);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn test_password_excludes_look_alikes() {
+        let profile = GeneratorProfile {
+            length: 200,
+            exclude_similar: true,
+            ..Default::default()
+        };
+        let password = generate(&profile).unwrap();
+        assert!(!password.chars().any(|c| LOOK_ALIKES.contains(c)));
+    }
+
+    #[test]
+    fn test_rejects_impossible_policy() {
+        let profile = GeneratorProfile {
+            length: 3,
+            min_per_class: 1,
+            ..Default::default()
+        };
+        assert!(generate(&profile).is_err());
+    }
+
+    #[test]
+    fn test_passphrase_word_count() {
+        let profile = GeneratorProfile {
+            passphrase: true,
+            words: 5,
+            separator: " ".to_string(),
+            ..Default::default()
+        };
+        let passphrase = generate(&profile).unwrap();
+        assert_eq!(passphrase.split(' ').count(), 5);
+    }
+}
@@ -0,0 +1,158 @@
+//! Git credential helper (see `git help credential`). Git passes the
+//! credential attributes as `key=value` lines on stdin; `get` answers with
+//! the user name and password of the matching entry, `store` and `erase`
+//! update the database.
+
+use anyhow::{anyhow, Context, Result};
+use keepass::db::Entry;
+use std::io::BufRead;
+use url::Url;
+
+use crate::search::UrlQuery;
+
+/// Group for entries created by `store`, unless configured.
+pub const DEFAULT_GROUP: &str = "/Git";
+
+/// The attributes of one credential request.
+#[derive(Debug, Default, PartialEq)]
+pub struct CredentialRequest {
+    pub protocol: Option<String>,
+    pub host: Option<String>,
+    pub path: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl CredentialRequest {
+    /// Reads attributes up to the first blank line or end of input. Unknown
+    /// attributes are ignored as the protocol requires.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut request = Self::default();
+        for line in reader.lines() {
+            let line = line.context("Failed to read credential request")?;
+            if line.is_empty() {
+                break;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid credential attribute: {}", line))?;
+            let value = Some(value.to_string());
+            match key {
+                "protocol" => request.protocol = value,
+                "host" => request.host = value,
+                "path" => request.path = value,
+                "username" => request.username = value,
+                "password" => request.password = value,
+                "url" => request.apply_url(value.as_deref().unwrap_or_default())?,
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+
+    /// Splits a `url` attribute into its components.
+    fn apply_url(&mut self, value: &str) -> Result<()> {
+        let url = Url::parse(value).with_context(|| format!("Invalid url attribute: {}", value))?;
+        self.protocol = Some(url.scheme().to_string());
+        self.host = url.host_str().map(|host| match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        });
+        let path = url.path().trim_start_matches('/');
+        self.path = (!path.is_empty()).then(|| path.to_string());
+        if !url.username().is_empty() {
+            self.username = Some(url.username().to_string());
+        }
+        if let Some(password) = url.password() {
+            self.password = Some(password.to_string());
+        }
+        Ok(())
+    }
+
+    /// The lookup for this request; `None` without a host (e.g. `file://`).
+    pub fn query(&self) -> Option<UrlQuery> {
+        let host = self.host.clone().filter(|h| !h.is_empty())?;
+        Some(UrlQuery {
+            protocol: self.protocol.clone(),
+            host,
+            path: self.path.clone(),
+            username: self.username.clone(),
+        })
+    }
+
+    /// URL stored in entries created by `store`.
+    pub fn entry_url(&self) -> String {
+        let mut url = format!(
+            "{}://{}",
+            self.protocol.as_deref().unwrap_or("https"),
+            self.host.as_deref().unwrap_or_default()
+        );
+        if let Some(path) = &self.path {
+            url.push('/');
+            url.push_str(path.trim_start_matches('/'));
+        }
+        url
+    }
+
+    /// Path of the entry created by `store` in `group`: `user@host`, or just
+    /// the host when git did not send a user name.
+    pub fn entry_path(&self, group: &str) -> String {
+        // Port separators are fine in titles, path separators are not
+        let host = self.host.as_deref().unwrap_or_default().replace('/', "_");
+        let title = match &self.username {
+            Some(username) => format!("{}@{}", username, host),
+            None => host,
+        };
+        format!("{}/{}", group.trim_end_matches('/'), title)
+    }
+}
+
+/// Formats the answer to `get` for `entry`.
+pub fn format_response(entry: &Entry) -> String {
+    let mut response = String::new();
+    if let Some(username) = entry.get_username().filter(|u| !u.is_empty()) {
+        response.push_str(&format!("username={}\n", username));
+    }
+    if let Some(password) = entry.get_password() {
+        response.push_str(&format!("password={}\n", password));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_request() {
+        let input = "protocol=https\nhost=gitlab.example.com:8443\npath=group/repo.git\nusername=deploy\n\nignored=1\n";
+        let request = CredentialRequest::read(input.as_bytes()).unwrap();
+        assert_eq!(request.host.as_deref(), Some("gitlab.example.com:8443"));
+        assert_eq!(request.username.as_deref(), Some("deploy"));
+        assert_eq!(request.password, None);
+        assert_eq!(
+            request.entry_url(),
+            "https://gitlab.example.com:8443/group/repo.git"
+        );
+        assert_eq!(
+            request.entry_path("/Git/"),
+            "/Git/deploy@gitlab.example.com:8443"
+        );
+    }
+
+    #[test]
+    fn test_read_url_attribute() {
+        let input = "url=https://alice@github.com/org/repo.git\n";
+        let request = CredentialRequest::read(input.as_bytes()).unwrap();
+        assert_eq!(
+            request,
+            CredentialRequest {
+                protocol: Some("https".to_string()),
+                host: Some("github.com".to_string()),
+                path: Some("org/repo.git".to_string()),
+                username: Some("alice".to_string()),
+                password: None,
+            }
+        );
+    }
+}
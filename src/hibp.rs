@@ -0,0 +1,208 @@
+//! Offline check of passwords against a downloaded Have I Been Pwned list:
+//! either a directory of range files (`00000.txt` ... `FFFFF.txt`, lines of
+//! `SUFFIX:COUNT`) or one file of `HASH:COUNT` lines sorted by hash. Nothing
+//! is sent over the network.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::output::{print_structured, OutputFormat};
+use crate::search::Finder;
+
+/// Length of an SHA-1 hash in hex digits, and of the range prefix.
+const HASH_LEN: usize = 40;
+const PREFIX_LEN: usize = 5;
+
+#[derive(Debug, Serialize)]
+pub struct Compromised {
+    pub path: String,
+    pub uuid: String,
+    /// How often the password appears in breaches.
+    pub count: u64,
+}
+
+pub enum HashList {
+    RangeDir(PathBuf),
+    Sorted { reader: BufReader<File>, len: u64 },
+}
+
+impl HashList {
+    pub fn open(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            return Ok(HashList::RangeDir(path.to_path_buf()));
+        }
+        let file =
+            File::open(path).with_context(|| format!("Failed to open hash list {:?}", path))?;
+        let len = file.metadata()?.len();
+        Ok(HashList::Sorted {
+            reader: BufReader::new(file),
+            len,
+        })
+    }
+
+    /// Returns the breach count of an upper-case hex SHA-1 hash, `None` if
+    /// the list does not contain it.
+    pub fn lookup(&mut self, hash: &str) -> Result<Option<u64>> {
+        match self {
+            HashList::RangeDir(dir) => lookup_range(dir, hash),
+            HashList::Sorted { reader, len } => lookup_sorted(reader, *len, hash),
+        }
+    }
+}
+
+/// Checks the password of every entry outside the Recycle Bin; each distinct
+/// password is looked up once.
+pub fn check(finder: &Finder, list: &mut HashList) -> Result<Vec<Compromised>> {
+    let mut by_hash: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    finder.for_each_entry(&mut |path, entry| {
+        let password = entry.get_password().unwrap_or_default();
+        if !password.is_empty() {
+            by_hash
+                .entry(sha1_hex(password))
+                .or_default()
+                .push((path.to_string(), entry.uuid.to_string()));
+        }
+    });
+
+    let mut compromised = Vec::new();
+    for (hash, entries) in by_hash {
+        if let Some(count) = list.lookup(&hash)? {
+            for (path, uuid) in entries {
+                compromised.push(Compromised { path, uuid, count });
+            }
+        }
+    }
+    compromised.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(compromised)
+}
+
+fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// Parses a `HASH:COUNT` line; the hash is compared ignoring case.
+fn parse_line(line: &str) -> Option<(String, u64)> {
+    let (hash, count) = line.trim_end().split_once(':')?;
+    Some((hash.to_ascii_uppercase(), count.trim().parse().unwrap_or(1)))
+}
+
+fn lookup_range(dir: &Path, hash: &str) -> Result<Option<u64>> {
+    let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+    let path = [format!("{}.txt", prefix), prefix.to_string()]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| anyhow!("Range file {}.txt missing in {:?}", prefix, dir))?;
+    let file = File::open(&path).with_context(|| format!("Failed to open {:?}", path))?;
+    for line in BufReader::new(file).lines() {
+        if let Some((candidate, count)) = parse_line(&line?) {
+            if candidate == suffix {
+                return Ok(Some(count));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Binary search over the byte offsets of a file sorted by hash. Only the
+/// lines visited are read.
+fn lookup_sorted<R: BufRead + Seek>(reader: &mut R, len: u64, hash: &str) -> Result<Option<u64>> {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let (start, line) = line_at_or_after(reader, mid)?;
+        if line.is_empty() || start >= high {
+            high = mid;
+            continue;
+        }
+        let (candidate, count) = parse_line(&line)
+            .filter(|(candidate, _)| candidate.len() == HASH_LEN)
+            .ok_or_else(|| anyhow!("Invalid line in hash list at byte {}", start))?;
+        match candidate.as_str().cmp(hash) {
+            std::cmp::Ordering::Equal => return Ok(Some(count)),
+            std::cmp::Ordering::Less => low = start + line.len() as u64,
+            std::cmp::Ordering::Greater => high = mid,
+        }
+    }
+    Ok(None)
+}
+
+/// Reads the first line starting at or after `offset`; an empty line at the
+/// end of the file.
+fn line_at_or_after<R: BufRead + Seek>(reader: &mut R, offset: u64) -> Result<(u64, String)> {
+    let mut start = offset;
+    if offset > 0 {
+        // Skip the rest of the line that contains the byte before `offset`
+        reader.seek(SeekFrom::Start(offset - 1))?;
+        start = offset - 1 + reader.read_until(b'\n', &mut Vec::new())? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    Ok((start, line))
+}
+
+pub fn print_report(items: &[Compromised], format: &OutputFormat) -> Result<()> {
+    if !matches!(format, OutputFormat::Text) {
+        return print_structured(&items, format);
+    }
+    if items.is_empty() {
+        println!("No compromised passwords found");
+    }
+    for item in items {
+        println!("{:>10}  {}", item.count, item.path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_lookup_sorted() {
+        let mut hashes: Vec<String> = ["password", "123456", "letmein", "qwerty", "dragon"]
+            .iter()
+            .map(|p| sha1_hex(p))
+            .collect();
+        hashes.sort();
+        let dump: String = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, h)| format!("{}:{}\r\n", h, i + 1))
+            .collect();
+        let len = dump.len() as u64;
+        let mut reader = Cursor::new(dump.into_bytes());
+
+        for (i, hash) in hashes.iter().enumerate() {
+            assert_eq!(
+                lookup_sorted(&mut reader, len, hash).unwrap(),
+                Some(i as u64 + 1)
+            );
+        }
+        assert_eq!(
+            sha1_hex("password"),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+        let missing = sha1_hex("correct horse battery staple");
+        assert_eq!(lookup_sorted(&mut reader, len, &missing).unwrap(), None);
+        assert_eq!(
+            lookup_sorted(&mut reader, len, &"0".repeat(40)).unwrap(),
+            None
+        );
+        assert_eq!(
+            lookup_sorted(&mut reader, len, &"F".repeat(40)).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+use keepass::db::Entry;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::output::{print_structured, OutputFormat, TIME_FORMAT};
+
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    /// 0 is the current version, 1 the one before it, and so on.
+    pub version: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    /// Fields that differ from the next older version.
+    pub changed: Vec<String>,
+}
+
+/// Returns the current version followed by the history, newest first.
+pub fn versions(entry: &Entry) -> Vec<&Entry> {
+    let mut versions = vec![entry];
+    if let Some(history) = &entry.history {
+        // KeePass stores the history oldest first; sort by time in case the
+        // file does not follow that order.
+        let mut previous: Vec<&Entry> = history.get_entries().iter().rev().collect();
+        previous.sort_by_key(|e| std::cmp::Reverse(e.times.last_modification.as_ref()));
+        versions.extend(previous);
+    }
+    versions
+}
+
+/// Returns the `n`th previous version of `entry` (1 is the latest one).
+pub fn previous_version(entry: &Entry, n: usize) -> Result<Entry> {
+    let versions = versions(entry);
+    let count = versions.len() - 1;
+    if n == 0 {
+        return Ok(entry.clone());
+    }
+    versions.get(n).map(|v| (*v).clone()).ok_or_else(|| {
+        anyhow!(
+            "Entry has {} previous version{}, cannot go back {}",
+            count,
+            if count == 1 { "" } else { "s" },
+            n
+        )
+    })
+}
+
+/// Names of the fields whose values differ between two versions.
+pub fn changed_fields(newer: &Entry, older: &Entry) -> Vec<String> {
+    let names: BTreeSet<&String> = newer.fields.keys().chain(older.fields.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| newer.get(name) != older.get(name))
+        .cloned()
+        .collect()
+}
+
+pub fn list(entry: &Entry) -> Vec<VersionInfo> {
+    let versions = versions(entry);
+    versions
+        .iter()
+        .enumerate()
+        .map(|(index, version)| VersionInfo {
+            version: index,
+            modified: version
+                .times
+                .last_modification
+                .as_ref()
+                .map(|t| t.format(TIME_FORMAT).to_string()),
+            changed: versions
+                .get(index + 1)
+                .map(|older| changed_fields(version, older))
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+pub fn print_list(items: &[VersionInfo], format: &OutputFormat) -> Result<()> {
+    if !matches!(format, OutputFormat::Text) {
+        return print_structured(&items, format);
+    }
+    let last = items.len().saturating_sub(1);
+    for item in items {
+        let changed = if item.version == last {
+            "(oldest)".to_string()
+        } else if item.changed.is_empty() {
+            "-".to_string()
+        } else {
+            item.changed.join(", ")
+        };
+        println!(
+            "{:>3}  {:19}  {}",
+            item.version,
+            item.modified.as_deref().unwrap_or("-"),
+            changed
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use keepass::db::{History, Value};
+
+    fn version(password: &str, day: u32) -> Entry {
+        let mut entry = Entry::new();
+        entry
+            .fields
+            .insert("Title".to_string(), Value::Unprotected("db".to_string()));
+        entry.fields.insert(
+            "Password".to_string(),
+            Value::Unprotected(password.to_string()),
+        );
+        let time = NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        entry.times.last_modification = Some(time);
+        entry
+    }
+
+    #[test]
+    fn test_previous_versions_newest_first() {
+        let mut entry = version("three", 3);
+        let mut history = History::default();
+        // add_entry inserts at the front, so this stores oldest first
+        history.add_entry(version("two", 2));
+        history.add_entry(version("one", 1));
+        entry.history = Some(history);
+
+        assert_eq!(
+            previous_version(&entry, 1).unwrap().get_password(),
+            Some("two")
+        );
+        assert_eq!(
+            previous_version(&entry, 2).unwrap().get_password(),
+            Some("one")
+        );
+        assert!(previous_version(&entry, 3).is_err());
+
+        let items = list(&entry);
+        assert_eq!(items[0].changed, vec!["Password".to_string()]);
+        assert_eq!(items[2].modified.as_deref(), Some("2024-01-01T00:00:00"));
+    }
+}
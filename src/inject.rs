@@ -0,0 +1,192 @@
+//! Template rendering for `inject`. Two reference forms are replaced:
+//!
+//! - `{{ kp "/Databases/prod" "Password" }}` (the field is optional)
+//! - `kp:///Databases/prod:UserName` (percent-encoded, field optional)
+//!
+//! Other `{{ ... }}` blocks are left untouched so templates of other tools
+//! pass through unchanged.
+
+use anyhow::{anyhow, Context, Result};
+
+const URI_SCHEME: &str = "kp://";
+
+/// Renders `template`, asking `resolve(item, field)` for every reference.
+/// For `kp://` references the field is `None` and `item` is the whole
+/// `ENTRY[:FIELD]` reference (see `exec::resolve_reference`). All failures
+/// are collected and reported together with their line number.
+pub fn render<F>(template: &str, name: &str, mut resolve: F) -> Result<String>
+where
+    F: FnMut(&str, Option<&str>) -> Result<String>,
+{
+    let mut output = String::with_capacity(template.len());
+    let mut errors = Vec::new();
+    let mut rest = template;
+    let mut offset = 0;
+
+    while let Some(start) = next_reference(rest) {
+        output.push_str(&rest[..start]);
+        let line = template[..offset + start].matches('\n').count() + 1;
+        let candidate = &rest[start..];
+
+        let parsed = if candidate.starts_with("{{") {
+            parse_action(candidate)
+        } else {
+            parse_uri(candidate).map(Some)
+        };
+
+        let consumed = match parsed {
+            Ok(Some((len, item, field))) => {
+                match resolve(&item, field.as_deref()) {
+                    Ok(value) => output.push_str(&value),
+                    Err(e) => errors.push(format!("{}:{}: {}: {:#}", name, line, item, e)),
+                }
+                len
+            }
+            Ok(None) => {
+                // Not ours: copy the opening braces and keep scanning
+                output.push_str("{{");
+                2
+            }
+            Err(e) => {
+                errors.push(format!("{}:{}: {}", name, line, e));
+                let len = candidate.find('\n').unwrap_or(candidate.len());
+                output.push_str(&candidate[..len]);
+                len
+            }
+        };
+
+        rest = &candidate[consumed..];
+        offset += start + consumed;
+    }
+    output.push_str(rest);
+
+    if !errors.is_empty() {
+        return Err(anyhow!(
+            "Failed to render template:\n  {}",
+            errors.join("\n  ")
+        ));
+    }
+    Ok(output)
+}
+
+fn next_reference(text: &str) -> Option<usize> {
+    match (text.find("{{"), text.find(URI_SCHEME)) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Parses `{{ kp "item" ["field"] }}`. Returns `None` for other actions.
+fn parse_action(text: &str) -> Result<Option<(usize, String, Option<String>)>> {
+    let end = match text.find("}}") {
+        Some(end) => end,
+        None => return Ok(None),
+    };
+    let inner = text[2..end].trim();
+    let args = match inner.strip_prefix("kp") {
+        Some(args) if args.starts_with(char::is_whitespace) => args,
+        _ => return Ok(None),
+    };
+
+    let mut words = Vec::new();
+    let mut chars = args.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c != '"' && c != '\'' {
+            return Err(anyhow!("kp arguments must be quoted strings: {}", inner));
+        }
+        let mut word = String::new();
+        let mut closed = false;
+        while let Some(next) = chars.next() {
+            match next {
+                '\\' if c == '"' => {
+                    if let Some(escaped) = chars.next() {
+                        word.push(escaped);
+                    }
+                }
+                q if q == c => {
+                    closed = true;
+                    break;
+                }
+                other => word.push(other),
+            }
+        }
+        if !closed {
+            return Err(anyhow!("Unterminated string in: {}", inner));
+        }
+        words.push(word);
+    }
+
+    match words.as_slice() {
+        [item] => Ok(Some((end + 2, item.clone(), Some("Password".to_string())))),
+        [item, field] => Ok(Some((end + 2, item.clone(), Some(field.clone())))),
+        _ => Err(anyhow!(
+            "kp expects an entry and an optional field: {}",
+            inner
+        )),
+    }
+}
+
+/// Parses a `kp://ENTRY[:FIELD]` reference up to the next whitespace or quote.
+fn parse_uri(text: &str) -> Result<(usize, String, Option<String>)> {
+    let len = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '`' | '<' | '>'))
+        .unwrap_or(text.len());
+    let reference = percent_decode(&text[URI_SCHEME.len()..len])?;
+    if reference.is_empty() {
+        return Err(anyhow!("Empty {} reference", URI_SCHEME));
+    }
+    Ok((len, reference, None))
+}
+
+fn percent_decode(text: &str) -> Result<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid percent-encoding in: {}", text))?;
+            decoded.push(hex);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).with_context(|| format!("Invalid UTF-8 in: {}", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake(item: &str, field: Option<&str>) -> Result<String> {
+        match item {
+            "/missing" => Err(anyhow!("no items found")),
+            _ => Ok(format!("<{}|{}>", item, field.unwrap_or("-"))),
+        }
+    }
+
+    #[test]
+    fn test_renders_both_reference_forms() {
+        let template = "user: {{ kp \"/db/prod\" \"UserName\" }}\npass: kp:///db/prod%3Aport\nhelm: {{ .Values.x }}\n";
+        let rendered = render(template, "t", fake).unwrap();
+        assert_eq!(
+            rendered,
+            "user: </db/prod|UserName>\npass: </db/prod:port|->\nhelm: {{ .Values.x }}\n"
+        );
+    }
+
+    #[test]
+    fn test_reports_line_numbers() {
+        let template = "a\nb: {{ kp \"/missing\" }}\nc: {{ kp unquoted }}\n";
+        let err = render(template, "app.yaml", fake).unwrap_err().to_string();
+        assert!(err.contains("app.yaml:2: /missing: no items found"));
+        assert!(err.contains("app.yaml:3:"));
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{anyhow, Result};
+use keepass::db::{Entry, Group};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::edit::split_group_path;
+use crate::output::{print_structured, OutputFormat, TIME_FORMAT};
+
+/// Returns the group at `path` (the root group for `/` or an empty path).
+pub fn find_group<'g>(root: &'g Group, path: &str) -> Result<&'g Group> {
+    let mut current = root;
+    for name in split_group_path(root, path) {
+        current = current
+            .groups
+            .iter()
+            .find(|g| g.name == name)
+            .ok_or_else(|| anyhow!("Group not found: {}", name))?;
+    }
+    Ok(current)
+}
+
+/// Normalizes a user-supplied group path to `/Group/Sub` form.
+pub fn group_path(root: &Group, path: &str) -> String {
+    format!("/{}", split_group_path(root, path).join("/"))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListItem {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub path: String,
+    /// Entry title or group name; titles may contain `/`.
+    #[serde(skip)]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TreeNode {
+    pub name: String,
+    pub path: String,
+    pub groups: Vec<TreeNode>,
+    pub entries: Vec<ListItem>,
+}
+
+fn join(parent: &str, name: &str) -> String {
+    format!("{}/{}", parent.trim_end_matches('/'), name)
+}
+
+fn entry_item(entry: &Entry, parent: &str) -> ListItem {
+    ListItem {
+        kind: "entry",
+        path: join(parent, entry.get_title().unwrap_or_default()),
+        name: entry.get_title().unwrap_or_default().to_string(),
+        username: entry.get_username().map(str::to_string),
+        url: entry.get_url().map(str::to_string),
+        modified: entry
+            .times
+            .last_modification
+            .as_ref()
+            .map(|t| t.format(TIME_FORMAT).to_string()),
+    }
+}
+
+/// Subgroups left out of listings: the Recycle Bin, unless `include` is set.
+/// The Recycle Bin can still be listed by naming it as the start group.
+pub fn hidden_group(recycle_bin: Option<Uuid>, include: bool) -> Option<Uuid> {
+    recycle_bin.filter(|_| !include)
+}
+
+fn subgroups(group: &Group, hidden: Option<Uuid>) -> Vec<&Group> {
+    group
+        .groups
+        .iter()
+        .filter(|g| Some(g.uuid) != hidden)
+        .collect()
+}
+
+/// Collects the subgroups and entries of `group`, descending at most
+/// `depth` levels (`None` for no limit) and skipping the `hidden` group.
+pub fn list(
+    group: &Group,
+    path: &str,
+    depth: Option<usize>,
+    hidden: Option<Uuid>,
+) -> Vec<ListItem> {
+    let mut items = Vec::new();
+    collect(group, path, depth, hidden, &mut items);
+    items
+}
+
+fn collect(
+    group: &Group,
+    path: &str,
+    depth: Option<usize>,
+    hidden: Option<Uuid>,
+    items: &mut Vec<ListItem>,
+) {
+    if depth == Some(0) {
+        return;
+    }
+    for child in subgroups(group, hidden) {
+        let child_path = join(path, &child.name);
+        items.push(ListItem {
+            kind: "group",
+            path: format!("{}/", child_path),
+            name: child.name.clone(),
+            username: None,
+            url: None,
+            modified: child
+                .times
+                .last_modification
+                .as_ref()
+                .map(|t| t.format(TIME_FORMAT).to_string()),
+        });
+        collect(child, &child_path, depth.map(|d| d - 1), hidden, items);
+    }
+    for entry in &group.entries {
+        items.push(entry_item(entry, path));
+    }
+}
+
+pub fn tree(group: &Group, path: &str, depth: Option<usize>, hidden: Option<Uuid>) -> TreeNode {
+    let descend = depth != Some(0);
+    TreeNode {
+        name: if path == "/" {
+            "/".to_string()
+        } else {
+            group.name.clone()
+        },
+        path: path.to_string(),
+        groups: if descend {
+            subgroups(group, hidden)
+                .into_iter()
+                .map(|g| tree(g, &join(path, &g.name), depth.map(|d| d - 1), hidden))
+                .collect()
+        } else {
+            Vec::new()
+        },
+        entries: if descend {
+            group.entries.iter().map(|e| entry_item(e, path)).collect()
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+fn details(item: &ListItem) -> String {
+    format!(
+        "{}  {}  {}",
+        item.username.as_deref().unwrap_or("-"),
+        item.url.as_deref().filter(|u| !u.is_empty()).unwrap_or("-"),
+        item.modified.as_deref().unwrap_or("-")
+    )
+}
+
+pub fn print_list(items: &[ListItem], long: bool, format: &OutputFormat) -> Result<()> {
+    if !matches!(format, OutputFormat::Text) {
+        return print_structured(&items, format);
+    }
+    print!("{}", render_list(items, long));
+    Ok(())
+}
+
+fn render_list(items: &[ListItem], long: bool) -> String {
+    let width = items.iter().map(|i| i.path.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for item in items {
+        if long && item.kind == "entry" {
+            out.push_str(&format!(
+                "{:width$}  {}\n",
+                item.path,
+                details(item),
+                width = width
+            ));
+        } else {
+            out.push_str(&format!("{}\n", item.path));
+        }
+    }
+    out
+}
+
+pub fn print_tree(node: &TreeNode, long: bool, format: &OutputFormat) -> Result<()> {
+    if !matches!(format, OutputFormat::Text) {
+        return print_structured(node, format);
+    }
+    print!("{}", render_tree(node, long));
+    Ok(())
+}
+
+fn render_tree(node: &TreeNode, long: bool) -> String {
+    let mut out = format!("{}\n", node.path);
+    draw(node, "", long, &mut out);
+    out
+}
+
+fn draw(node: &TreeNode, prefix: &str, long: bool, out: &mut String) {
+    let count = node.groups.len() + node.entries.len();
+    let mut index = 0;
+    for group in &node.groups {
+        index += 1;
+        let (branch, indent) = if index == count {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        out.push_str(&format!("{}{}{}/\n", prefix, branch, group.name));
+        draw(group, &format!("{}{}", prefix, indent), long, out);
+    }
+    for entry in &node.entries {
+        index += 1;
+        let branch = if index == count {
+            "└── "
+        } else {
+            "├── "
+        };
+        if long {
+            out.push_str(&format!(
+                "{}{}{}  ({})\n",
+                prefix,
+                branch,
+                entry.name,
+                details(entry)
+            ));
+        } else {
+            out.push_str(&format!("{}{}{}\n", prefix, branch, entry.name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::{create_entry, remove, FieldChanges};
+    use keepass::Database;
+
+    fn database() -> Database {
+        let mut db = Database::new(Default::default());
+        let changes = FieldChanges {
+            set: vec![("UserName", "admin")],
+            ..Default::default()
+        };
+        create_entry(&mut db, "/Servers/prod/db01", &changes).unwrap();
+        create_entry(&mut db, "/Servers/web", &FieldChanges::default()).unwrap();
+        create_entry(&mut db, "/old", &FieldChanges::default()).unwrap();
+        let uuid = db.root.entries[0].uuid;
+        remove(&mut db, &uuid, false).unwrap();
+        // A title containing the path separator
+        let servers = crate::edit::ensure_group(&mut db.root, &["Servers"]);
+        let mut entry = Entry::new();
+        entry
+            .fields
+            .insert("Title".to_string(), crate::edit::unprotected("a/b"));
+        servers.entries.push(entry);
+        db
+    }
+
+    fn paths(items: &[ListItem]) -> Vec<&str> {
+        items.iter().map(|i| i.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_list_depth_and_recycle_bin() {
+        let db = database();
+        let hidden = hidden_group(db.meta.recyclebin_uuid, false);
+
+        let items = list(&db.root, "/", Some(1), hidden);
+        assert_eq!(paths(&items), vec!["/Servers/"]);
+
+        let items = list(&db.root, "/", Some(2), hidden);
+        assert_eq!(
+            paths(&items),
+            vec![
+                "/Servers/",
+                "/Servers/prod/",
+                "/Servers/web",
+                "/Servers/a/b"
+            ]
+        );
+        let items = list(&db.root, "/", None, hidden);
+        assert_eq!(items.len(), 5);
+        assert_eq!(items[2].path, "/Servers/prod/db01");
+        assert_eq!(items[2].username.as_deref(), Some("admin"));
+
+        let items = list(&db.root, "/", Some(1), None);
+        assert_eq!(paths(&items), vec!["/Servers/", "/Recycle Bin/"]);
+
+        let bin = find_group(&db.root, "/Recycle Bin").unwrap();
+        let items = list(bin, "/Recycle Bin", None, hidden);
+        assert_eq!(paths(&items), vec!["/Recycle Bin/old"]);
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let db = database();
+        let hidden = hidden_group(db.meta.recyclebin_uuid, false);
+
+        let node = tree(&db.root, "/", None, hidden);
+        assert_eq!(
+            render_tree(&node, false),
+            "/\n\
+             └── Servers/\n\
+             \u{20}   ├── prod/\n\
+             \u{20}   │   └── db01\n\
+             \u{20}   ├── web\n\
+             \u{20}   └── a/b\n"
+        );
+
+        let node = tree(&db.root, "/", Some(1), hidden);
+        assert_eq!(render_tree(&node, false), "/\n└── Servers/\n");
+        assert!(tree(&db.root, "/", Some(0), hidden).groups.is_empty());
+    }
+
+    #[test]
+    fn test_render_list() {
+        let db = database();
+        let group = find_group(&db.root, "/Servers/prod").unwrap();
+        let items = list(group, "/Servers/prod", None, None);
+        let rendered = render_list(&items, true);
+        assert!(rendered.starts_with("/Servers/prod/db01  admin  -  "));
+        assert_eq!(render_list(&items, false), "/Servers/prod/db01\n");
+    }
+}
@@ -1,20 +1,51 @@
+mod agent;
 mod args;
+mod attachment;
+mod audit;
 mod config;
 mod db_helper;
+mod docker_credential;
+mod edit;
+mod exec;
+mod expiry;
+mod fuzzy;
+mod generator;
+mod git_credential;
+mod hibp;
+mod history;
+mod inject;
+mod listing;
 mod otp;
 mod output;
+mod picker;
+mod placeholder;
 mod search;
+mod ssh_agent;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Parser;
-use keepass::db::Entry;
+use keepass::db::{Entry, Value};
+use keepass::Database;
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use uuid::Uuid;
 
-use crate::args::Args;
+use crate::args::{Args, Command, GeneratorArgs};
 use crate::config::Config;
-use crate::db_helper::{open_database, resolve_password};
-use crate::output::{resolve_output_type, show_all_fields, Handler};
-use crate::search::{Finder, SearchOptions};
+use crate::db_helper::{
+    read_secret, resolve_keyfile, resolve_password, unlock_database, Credentials, Session,
+};
+use crate::docker_credential::RegistryCredentials;
+use crate::generator::GeneratorProfile;
+use crate::git_credential::CredentialRequest;
+use crate::output::{
+    print_results, print_structured, resolve_output_type, show_all_fields, write_private, Handler,
+    OutputFormat,
+};
+use crate::placeholder::{Fields, Resolver};
+use crate::search::{Finder, NotFound, SearchOptions, SearchResult, UrlQuery};
 
 fn main() {
     if let Err(e) = run() {
@@ -24,7 +55,7 @@ fn main() {
 }
 
 fn run() -> Result<()> {
-    let args = Args::parse();
+    let args = Args::parse_from(helper_args(std::env::args_os().collect()));
 
     // Handle background clipboard clearing
     if let Some(seconds) = args.clear_clipboard_after {
@@ -48,52 +79,70 @@ fn run() -> Result<()> {
         println!("Default Output: {:?}", config.default_output);
         println!("Password File: {:?}", config.password_file);
         println!("Password Executable: {:?}", config.password_executable);
+        println!("Key File: {:?}", config.keyfile_path);
         println!("Clipboard Timeout: {:?}", config.clipboard_timeout);
+        println!("Agent Idle Timeout: {:?}", config.agent_idle_timeout);
+        println!("Agent Lifetime: {:?}", config.agent_lifetime);
+        println!("Git Credential Group: {:?}", config.git_credential_group);
+        println!("Docker Registry Group: {:?}", config.docker_registry_group);
+        println!("SSH Agent Group: {:?}", config.ssh_agent_group);
+        println!("SSH Agent Tag: {:?}", config.ssh_agent_tag);
+        println!("Audit Min Entropy: {:?}", config.audit_min_entropy);
+        println!("Audit Max Age Days: {:?}", config.audit_max_age_days);
+        println!("HIBP Path: {:?}", config.hibp_path);
         println!("------------------------------------------");
         return Ok(());
     }
 
+    if let Some(command) = &args.command {
+        return run_command(command, &args, &config);
+    }
+
+    // With only tag filters given, every entry is searched
     let item = args
         .item
+        .clone()
+        .or_else(|| (!args.tags.is_empty()).then(String::new))
         .ok_or_else(|| anyhow!("item parameter is required"))?;
 
-    let db_path = args
-        .kdb_path
-        .or_else(|| std::env::var("KPASSCLI_KDBPATH").ok())
-        .or(config.database_path.clone())
-        .ok_or_else(|| anyhow!("no KeePass database path provided"))?;
-
-    let kdb_pass_env = std::env::var("KPASSCLI_KDBPASSWORD").ok();
-    let password = resolve_password(args.kdb_password, &config, kdb_pass_env)?;
-
-    let start = std::time::Instant::now();
-    let db = open_database(&db_path, &password)?;
-    if args.debug {
-        eprintln!("Database opened in: {:?}", start.elapsed());
-    }
-
-    let finder = Finder::new(
-        &db,
-        SearchOptions {
-            case_sensitive: args.case_sensitive,
-            exact_match: args.exact_match,
-        },
-    );
-
-    let results = finder.find(&item)?;
-
-    if results.is_empty() {
-        return Err(anyhow!("no items found"));
-    }
-
-    if results.len() > 1 {
+    let format = args.format;
+    // Plain lookups only output one field, so only that one is resolved
+    let fields = if args.show_all
+        || args.totp
+        || args.password_totp
+        || !matches!(format, OutputFormat::Text)
+    {
+        Fields::All
+    } else {
+        Fields::only(&[&args.field_name])
+    };
+    let mut lookup = Lookup::new(&args, &config)?.resolving(fields);
+    let mut results = lookup.find(&item)?;
+
+    // --history picks a version of one entry, so it always selects a result
+    if !matches!(format, OutputFormat::Text) && args.history.is_none() {
+        if results.is_empty() {
+            return Err(anyhow!("no items found"));
+        }
+        if args.first || args.index.is_some() {
+            results = vec![select_single(results, &args)?];
+        }
         for result in &results {
-            eprintln!("- {}", result.path);
+            expiry::check(result, args.strict_expiry)?;
         }
-        return Err(anyhow!("multiple items found"));
+        return print_results(&results, &format, args.reveal);
     }
 
-    let result = &results[0];
+    let mut result = select_single(results, &args)?;
+    if let Some(n) = args.history {
+        result.entry =
+            history::previous_version(&result.entry, n).with_context(|| result.path.clone())?;
+    }
+    let result = &result;
+    expiry::check(result, args.strict_expiry)?;
+    if !matches!(format, OutputFormat::Text) {
+        return print_results(std::slice::from_ref(result), &format, args.reveal);
+    }
 
     if args.show_all {
         show_all_fields(&result.entry);
@@ -103,10 +152,17 @@ fn run() -> Result<()> {
     let mut value = get_field_value(&result.entry, &args.field_name)?;
 
     if args.totp || args.password_totp {
-        let totp_url = get_field_value(&result.entry, "otp")
-            .map_err(|_| anyhow!("Entry has no TOTP configuration"))?;
-
-        let token = otp::generate_totp(&totp_url)?;
+        let otp = otp::Otp::from_entry(&result.entry)?
+            .ok_or_else(|| anyhow!("Entry has no TOTP configuration"))?;
+        let token = otp.generate()?;
+        if matches!(otp.kind, otp::Kind::Hotp { .. }) {
+            if args.history.is_some() {
+                return Err(anyhow!(
+                    "HOTP codes are only generated for the current version"
+                ));
+            }
+            lookup.increment_hotp_counter(&result.entry.uuid)?;
+        }
 
         if args.totp {
             value = token;
@@ -123,6 +179,770 @@ fn run() -> Result<()> {
     Ok(())
 }
 
+/// Finds entries through the agent when it holds the database, otherwise in
+/// the database opened on first use and kept open for further lookups.
+struct Lookup<'a> {
+    args: &'a Args,
+    config: &'a Config,
+    db_path: String,
+    session: Option<Session>,
+    /// Whether the entry history is needed; the agent leaves it out otherwise.
+    history: bool,
+    /// Fields whose references and placeholders are resolved.
+    fields: Fields,
+}
+
+impl<'a> Lookup<'a> {
+    fn new(args: &'a Args, config: &'a Config) -> Result<Self> {
+        Ok(Self {
+            args,
+            config,
+            db_path: resolve_db_path(args, config)?,
+            session: None,
+            history: args.history.is_some(),
+            fields: Fields::All,
+        })
+    }
+
+    fn with_history(mut self) -> Self {
+        self.history = true;
+        self
+    }
+
+    fn resolving(mut self, fields: Fields) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    fn find(&mut self, query: &str) -> Result<Vec<SearchResult>> {
+        let fields = self.fields.clone();
+        self.find_resolving(query, &fields)
+    }
+
+    fn find_resolving(&mut self, query: &str, fields: &Fields) -> Result<Vec<SearchResult>> {
+        if self.session.is_none() {
+            if let Some(results) = agent::find(
+                &self.db_path,
+                query,
+                &search_options(self.args),
+                fields,
+                self.history,
+            )? {
+                if self.args.debug {
+                    eprintln!("Lookup answered by agent");
+                }
+                return Ok(results);
+            }
+            self.session = Some(open_session(self.args, self.config)?);
+        }
+        let db = &self.session.as_ref().expect("session opened above").db;
+        let results = Finder::new(db, search_options(self.args)).find(query)?;
+        Ok(Resolver::new(db).resolve_results(results, fields, self.history))
+    }
+
+    fn find_single(&mut self, query: &str) -> Result<SearchResult> {
+        let result = select_single(self.find(query)?, self.args)?;
+        expiry::check(&result, self.args.strict_expiry)?;
+        Ok(result)
+    }
+
+    /// Reads `field` of the entry `query` refers to; `None` if the entry has
+    /// no such field.
+    fn read_field(&mut self, query: &str, field: &str) -> Result<Option<String>> {
+        let results = self.find_resolving(query, &Fields::only(&[field]))?;
+        let result = select_single(results, self.args)?;
+        expiry::check(&result, self.args.strict_expiry)?;
+        Ok(get_field_value(&result.entry, field).ok())
+    }
+
+    /// Stores the next HOTP counter of the entry with `uuid`, so that no
+    /// code is generated twice. The agent only reads, so this always opens
+    /// the database itself.
+    fn increment_hotp_counter(&mut self, uuid: &Uuid) -> Result<()> {
+        if self.session.is_none() {
+            self.session = Some(open_session(self.args, self.config)?);
+        }
+        let session = self.session.as_mut().expect("session opened above");
+        edit::modify_entry(&mut session.db, uuid, |entry| {
+            let next = otp::increment_counter(entry.get("otp").unwrap_or_default())?;
+            let value = match entry.fields.get("otp") {
+                Some(Value::Protected(_)) => edit::protected(&next),
+                _ => edit::unprotected(&next),
+            };
+            entry.fields.insert("otp".to_string(), value);
+            Ok(())
+        })?;
+        session.save()
+    }
+
+    /// Finds entries by URL, best match first.
+    fn find_url(&mut self, query: &UrlQuery) -> Result<Vec<SearchResult>> {
+        if self.session.is_none() {
+            if let Some(results) = agent::find_url(&self.db_path, query, &self.fields)? {
+                if self.args.debug {
+                    eprintln!("Lookup answered by agent");
+                }
+                return Ok(results);
+            }
+            self.session = Some(open_session(self.args, self.config)?);
+        }
+        let db = &self.session.as_ref().expect("session opened above").db;
+        let results = Finder::new(db, search_options(self.args)).find_by_url(query);
+        Ok(Resolver::new(db).resolve_results(results, &self.fields, false))
+    }
+}
+
+fn resolve_db_path(args: &Args, config: &Config) -> Result<String> {
+    args.kdb_path
+        .clone()
+        .or_else(|| std::env::var("KPASSCLI_KDBPATH").ok())
+        .or(config.database_path.clone())
+        .ok_or_else(|| anyhow!("no KeePass database path provided"))
+}
+
+fn open_session(args: &Args, config: &Config) -> Result<Session> {
+    let db_path = resolve_db_path(args, config)?;
+
+    let keyfile = resolve_keyfile(
+        args.keyfile.clone(),
+        config,
+        std::env::var("KPASSCLI_KEYFILE").ok(),
+    );
+    let kdb_pass_env = std::env::var("KPASSCLI_KDBPASSWORD").ok();
+    let password = resolve_password(
+        args.kdb_password.clone(),
+        config,
+        kdb_pass_env,
+        keyfile.as_deref(),
+    )?;
+    let mut credentials = Credentials { password, keyfile };
+
+    let start = std::time::Instant::now();
+    let db = unlock_database(&db_path, &mut credentials)?;
+    if args.debug {
+        eprintln!("Database opened in: {:?}", start.elapsed());
+    }
+
+    Ok(Session {
+        db,
+        path: db_path,
+        credentials,
+    })
+}
+
+fn run_command(command: &Command, args: &Args, config: &Config) -> Result<()> {
+    match command {
+        Command::Add(add) => {
+            check_generator_args(add.generate, &add.generator)?;
+            let mut session = open_session(args, config)?;
+            let password = if add.generate {
+                generate_password(&add.generator, config)?
+            } else {
+                read_secret(
+                    add.password_from.as_deref(),
+                    add.password_stdin,
+                    "Enter entry password: ",
+                )?
+            };
+            let password = (!password.is_empty()).then_some(password);
+            let path = edit::add_entry(&mut session.db, add, password.as_deref())?;
+            session.save()?;
+            eprintln!("Entry created: {}", path);
+            Ok(())
+        }
+        Command::Edit(edit_args) => {
+            check_generator_args(edit_args.generate, &edit_args.generator)?;
+            let mut session = open_session(args, config)?;
+            let result = find_single(&session.db, &edit_args.entry, args)?;
+            let password = if edit_args.generate {
+                Some(generate_password(&edit_args.generator, config)?)
+            } else if edit_args.password_from.is_some() || edit_args.password_stdin {
+                Some(read_secret(
+                    edit_args.password_from.as_deref(),
+                    edit_args.password_stdin,
+                    "Enter entry password: ",
+                )?)
+            } else {
+                None
+            };
+            edit::edit_entry(
+                &mut session.db,
+                &result.entry.uuid,
+                edit_args,
+                password.as_deref(),
+            )?;
+            session.save()?;
+            eprintln!("Entry updated: {}", result.path);
+            Ok(())
+        }
+        Command::Mv(mv) => {
+            let mut session = open_session(args, config)?;
+            let result = find_single(&session.db, &mv.entry, args)?;
+            let path = edit::move_entry(&mut session.db, &result.entry.uuid, &mv.group)?;
+            session.save()?;
+            eprintln!("Entry moved: {} -> {}", result.path, path);
+            Ok(())
+        }
+        Command::Rm(rm) => {
+            let mut session = open_session(args, config)?;
+            let result = find_single(&session.db, &rm.entry, args)?;
+            let recycled = edit::remove(&mut session.db, &result.entry.uuid, rm.force)?;
+            session.save()?;
+            if recycled {
+                eprintln!("Entry moved to Recycle Bin: {}", result.path);
+            } else {
+                eprintln!("Entry deleted: {}", result.path);
+            }
+            Ok(())
+        }
+        Command::Generate(gen) => {
+            let password = generate_password(&gen.generator, config)?;
+            let output_type = resolve_output_type(args.out.clone(), args.clipboard, config);
+            let handler = Handler::new(output_type, config.clipboard_timeout);
+            handler.output(&password)
+        }
+        Command::Ls(ls) => {
+            let format = args.format;
+            let session = open_session(args, config)?;
+            let group = listing::find_group(&session.db.root, &ls.group)?;
+            let path = listing::group_path(&session.db.root, &ls.group);
+            let depth = match (ls.depth, ls.recursive) {
+                (Some(depth), _) => Some(depth),
+                (None, true) => None,
+                (None, false) => Some(1),
+            };
+            let hidden = listing::hidden_group(session.db.meta.recyclebin_uuid, ls.recycle_bin);
+            let items = listing::list(group, &path, depth, hidden);
+            listing::print_list(&items, ls.long, &format)
+        }
+        Command::Audit(audit_args) => {
+            let format = args.format;
+            let session = open_session(args, config)?;
+            let finder = Finder::new(&session.db, SearchOptions::default());
+            let thresholds = audit::Thresholds {
+                min_entropy: audit_args
+                    .min_entropy
+                    .or(config.audit_min_entropy)
+                    .unwrap_or(audit::DEFAULT_MIN_ENTROPY),
+                max_age_days: audit_args
+                    .max_age
+                    .or(config.audit_max_age_days)
+                    .unwrap_or(audit::DEFAULT_MAX_AGE_DAYS),
+            };
+            audit::print_report(&audit::audit(&finder, &thresholds), &format)
+        }
+        Command::Hibp(hibp_args) => {
+            let format = args.format;
+            let path = hibp_args
+                .list
+                .as_ref()
+                .or(config.hibp_path.as_ref())
+                .ok_or_else(|| anyhow!("No hash list given and no hibp_path configured"))?;
+            let mut list = hibp::HashList::open(Path::new(path))?;
+            let session = open_session(args, config)?;
+            let finder = Finder::new(&session.db, SearchOptions::default());
+            hibp::print_report(&hibp::check(&finder, &mut list)?, &format)
+        }
+        Command::Expiring(expiring) => {
+            let format = args.format;
+            let session = open_session(args, config)?;
+            let finder = Finder::new(&session.db, SearchOptions::default());
+            let items = expiry::report(&finder, &expiring.group, expiring.days);
+            expiry::print_report(&items, &format)?;
+            match expiry::exit_code(&items) {
+                0 => Ok(()),
+                code => process::exit(code),
+            }
+        }
+        Command::Tree(tree) => {
+            let format = args.format;
+            let session = open_session(args, config)?;
+            let group = listing::find_group(&session.db.root, &tree.group)?;
+            let path = listing::group_path(&session.db.root, &tree.group);
+            let hidden = listing::hidden_group(session.db.meta.recyclebin_uuid, tree.recycle_bin);
+            let node = listing::tree(group, &path, tree.depth, hidden);
+            listing::print_tree(&node, tree.long, &format)
+        }
+        Command::History(history_args) => {
+            let format = args.format;
+            // Only the field names are listed, so nothing needs resolving
+            let result = Lookup::new(args, config)?
+                .with_history()
+                .resolving(Fields::Only(Vec::new()))
+                .find_single(&history_args.entry)?;
+            history::print_list(&history::list(&result.entry), &format)
+        }
+        Command::Attachments(attach_args) => {
+            let mut session = open_session(args, config)?;
+            let result = find_single(&session.db, &attach_args.entry, args)?;
+            if let Some(file) = &attach_args.attach {
+                let data = std::fs::read(file)
+                    .with_context(|| format!("Failed to read file: {}", file))?;
+                let name = match &attach_args.name {
+                    Some(name) => name.clone(),
+                    None => attachment::file_name(file)?,
+                };
+                let size = data.len();
+                let replaced =
+                    attachment::attach(&mut session.db, &result.entry.uuid, &name, data)?;
+                session.save()?;
+                let action = if replaced { "replaced" } else { "added" };
+                eprintln!(
+                    "Attachment {}: {} ({} bytes) on {}",
+                    action, name, size, result.path
+                );
+                return Ok(());
+            }
+            let Some(name) = &attach_args.name else {
+                let format = args.format;
+                return attachment::print_list(&attachment::list(&result.entry), &format);
+            };
+            if attach_args.remove {
+                attachment::detach(&mut session.db, &result.entry.uuid, name)?;
+                session.save()?;
+                eprintln!("Attachment removed: {} from {}", name, result.path);
+                return Ok(());
+            }
+            let data = attachment::find(&result.entry, name)?;
+            match &attach_args.output {
+                Some(path) => {
+                    write_private(path, data)?;
+                    eprintln!("Attachment written: {} ({} bytes)", path, data.len());
+                    Ok(())
+                }
+                None => {
+                    std::io::stdout().write_all(data)?;
+                    Ok(())
+                }
+            }
+        }
+        Command::Exec(exec_args) => {
+            let mut mappings = Vec::new();
+            for file in &exec_args.env_file {
+                mappings.extend(exec::load_env_file(file)?);
+            }
+            for spec in &exec_args.env {
+                mappings.push(exec::parse_mapping(spec)?);
+            }
+
+            let mut lookup = Lookup::new(args, config)?;
+            let mut vars = Vec::new();
+            for mapping in &mappings {
+                let value = exec::resolve_reference(&mapping.reference, |item, field| {
+                    lookup.read_field(item, field)
+                })
+                .with_context(|| format!("Failed to resolve {}", mapping.name))?;
+                vars.push((mapping.name.clone(), value));
+            }
+            // The database is not needed by the child; drop it before spawning
+            drop(lookup);
+
+            let code = exec::run_child(&exec_args.command, &vars)?;
+            process::exit(code);
+        }
+        Command::Inject(inject_args) => {
+            let (template, name) = if inject_args.template == "-" {
+                let mut template = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut template)
+                    .context("Failed to read template from stdin")?;
+                (template, "<stdin>")
+            } else {
+                let template =
+                    std::fs::read_to_string(&inject_args.template).with_context(|| {
+                        format!("Failed to read template: {}", inject_args.template)
+                    })?;
+                (template, inject_args.template.as_str())
+            };
+
+            let mut lookup = Lookup::new(args, config)?;
+            let rendered = inject::render(&template, name, |item, field| match field {
+                Some(field) => lookup
+                    .read_field(item, field)?
+                    .ok_or_else(|| anyhow!("Field '{}' not found", field)),
+                None => exec::resolve_reference(item, |item, field| lookup.read_field(item, field)),
+            })?;
+
+            match &inject_args.output {
+                Some(path) => write_private(path, rendered.as_bytes()),
+                None => {
+                    print!("{}", rendered);
+                    Ok(())
+                }
+            }
+        }
+        Command::Agent(agent_args) => {
+            let idle_timeout = agent_args
+                .idle_timeout
+                .or(config.agent_idle_timeout)
+                .unwrap_or(agent::DEFAULT_IDLE_TIMEOUT);
+            let lifetime = agent_args
+                .lifetime
+                .or(config.agent_lifetime)
+                .unwrap_or(agent::DEFAULT_LIFETIME);
+            if agent_args.serve_stdin {
+                return agent::serve_from_stdin(idle_timeout, lifetime);
+            }
+            let session = open_session(args, config)?;
+            agent::spawn(&session, idle_timeout, lifetime)?;
+            eprintln!(
+                "Agent started for {} (socket {:?}, idle timeout {}s, lifetime {}s)",
+                session.path,
+                agent::socket_path(),
+                idle_timeout,
+                lifetime
+            );
+            Ok(())
+        }
+        Command::Lock => {
+            if agent::lock()? {
+                eprintln!("Agent locked");
+            } else {
+                eprintln!("No agent running");
+            }
+            Ok(())
+        }
+        Command::GitCredential(git) => {
+            let request = CredentialRequest::read(std::io::stdin().lock())?;
+            let query = match request.query() {
+                Some(query) => query,
+                None => return Ok(()),
+            };
+            match git.action.as_str() {
+                "get" => {
+                    let results = Lookup::new(args, config)?
+                        .resolving(Fields::only(&["UserName", "Password"]))
+                        .find_url(&query)?;
+                    if let Some(result) = results.first() {
+                        print!("{}", git_credential::format_response(&result.entry));
+                    }
+                    Ok(())
+                }
+                "store" => {
+                    let password = match &request.password {
+                        Some(password) => password,
+                        None => return Ok(()),
+                    };
+                    let mut session = open_session(args, config)?;
+                    let existing = Finder::new(&session.db, search_options(args))
+                        .find_by_url(&query)
+                        .into_iter()
+                        .next();
+                    let mut changes = edit::FieldChanges::default();
+                    if let Some(username) = &request.username {
+                        changes.set.push(("UserName", username));
+                    }
+                    changes.set.push(("Password", password));
+                    match existing {
+                        Some(result) => {
+                            if edit::update_entry(&mut session.db, &result.entry.uuid, &changes)? {
+                                session.save()?;
+                                eprintln!("Entry updated: {}", result.path);
+                            }
+                        }
+                        None => {
+                            let group = git
+                                .group
+                                .as_deref()
+                                .or(config.git_credential_group.as_deref())
+                                .unwrap_or(git_credential::DEFAULT_GROUP);
+                            let url = request.entry_url();
+                            changes.set.push(("URL", &url));
+                            let path = edit::create_entry(
+                                &mut session.db,
+                                &request.entry_path(group),
+                                &changes,
+                            )?;
+                            session.save()?;
+                            eprintln!("Entry created: {}", path);
+                        }
+                    }
+                    Ok(())
+                }
+                "erase" => {
+                    let mut session = open_session(args, config)?;
+                    let results =
+                        Finder::new(&session.db, search_options(args)).find_by_url(&query);
+                    // Git sends the rejected password; leave entries that were updated since
+                    let result = results.into_iter().find(|r| {
+                        request.password.is_none()
+                            || r.entry.get_password() == request.password.as_deref()
+                    });
+                    if let Some(result) = result {
+                        let recycled = edit::remove(&mut session.db, &result.entry.uuid, false)?;
+                        session.save()?;
+                        if recycled {
+                            eprintln!("Entry moved to Recycle Bin: {}", result.path);
+                        } else {
+                            eprintln!("Entry deleted: {}", result.path);
+                        }
+                    }
+                    Ok(())
+                }
+                // Unknown operations must be ignored for forward compatibility
+                _ => Ok(()),
+            }
+        }
+        Command::SshAgent(ssh) => {
+            let socket = ssh
+                .socket
+                .as_ref()
+                .map(PathBuf::from)
+                .unwrap_or_else(ssh_agent::socket_path);
+            if ssh.stop {
+                if ssh_agent::stop(&socket)? {
+                    eprintln!("SSH agent stopped");
+                } else {
+                    eprintln!("No SSH agent running");
+                }
+                return Ok(());
+            }
+            if ssh.serve_stdin {
+                return ssh_agent::serve_from_stdin(&socket);
+            }
+
+            let selection = ssh_agent::Selection {
+                group: ssh.group.as_deref().or(config.ssh_agent_group.as_deref()),
+                tag: ssh.tag.as_deref().or(config.ssh_agent_tag.as_deref()),
+                confirm: ssh.confirm,
+                lifetime: ssh.key_lifetime,
+            };
+            let keys = {
+                let session = open_session(args, config)?;
+                ssh_agent::load_keys(&session.db, &selection)?
+            };
+            if keys.is_empty() {
+                return Err(anyhow!("No SSH keys found in the selected entries"));
+            }
+            for key in &keys {
+                eprintln!("Loaded key: {}", key.comment);
+            }
+
+            if !ssh.foreground {
+                ssh_agent::spawn(&keys, &socket)?;
+            }
+            // Same output as ssh-agent, for eval "$(kpasscli ssh-agent)"
+            println!("SSH_AUTH_SOCK={}; export SSH_AUTH_SOCK;", socket.display());
+            if ssh.foreground {
+                std::io::stdout().flush()?;
+                return ssh_agent::serve(&keys, &socket);
+            }
+            Ok(())
+        }
+        Command::DockerCredential(docker) => {
+            let group = docker
+                .group
+                .as_deref()
+                .or(config.docker_registry_group.as_deref())
+                .unwrap_or(docker_credential::DEFAULT_GROUP);
+            let mut input = String::new();
+            if docker.action != "list" {
+                std::io::stdin()
+                    .read_to_string(&mut input)
+                    .context("Failed to read request from stdin")?;
+            }
+            let server_url = input.trim();
+            match docker.action.as_str() {
+                "get" => {
+                    let mut lookup = Lookup::new(args, config)?
+                        .resolving(Fields::only(&["UserName", "Password"]));
+                    let mut results =
+                        lookup.find_url(&docker_credential::registry_query(server_url)?)?;
+                    results.retain(|result| docker_credential::in_group(group, result));
+                    if results.is_empty() {
+                        let path = docker_credential::entry_path(group, server_url)?;
+                        results = match lookup.find_single(&path) {
+                            Ok(result) => vec![result],
+                            Err(e) if search::is_not_found(&e) => Vec::new(),
+                            Err(e) => return Err(e),
+                        };
+                    }
+                    match results.first() {
+                        Some(result) => print_structured(
+                            &RegistryCredentials {
+                                server_url: server_url.to_string(),
+                                username: result
+                                    .entry
+                                    .get_username()
+                                    .unwrap_or_default()
+                                    .to_string(),
+                                secret: result.entry.get_password().unwrap_or_default().to_string(),
+                            },
+                            &OutputFormat::Json,
+                        ),
+                        None => {
+                            println!("{}", docker_credential::NOT_FOUND);
+                            process::exit(1);
+                        }
+                    }
+                }
+                "store" => {
+                    let credentials: RegistryCredentials = serde_json::from_str(&input)
+                        .context("Failed to parse credentials from stdin")?;
+                    let mut session = open_session(args, config)?;
+                    let existing =
+                        find_registry(&session.db, group, &credentials.server_url, args)?;
+                    let mut changes = edit::FieldChanges::default();
+                    changes.set.push(("UserName", &credentials.username));
+                    changes.set.push(("Password", &credentials.secret));
+                    match existing {
+                        Some(result) => {
+                            if edit::update_entry(&mut session.db, &result.entry.uuid, &changes)? {
+                                session.save()?;
+                                eprintln!("Entry updated: {}", result.path);
+                            }
+                        }
+                        None => {
+                            changes.set.push(("URL", &credentials.server_url));
+                            let path = edit::create_entry(
+                                &mut session.db,
+                                &docker_credential::entry_path(group, &credentials.server_url)?,
+                                &changes,
+                            )?;
+                            session.save()?;
+                            eprintln!("Entry created: {}", path);
+                        }
+                    }
+                    Ok(())
+                }
+                "erase" => {
+                    let mut session = open_session(args, config)?;
+                    match find_registry(&session.db, group, server_url, args)? {
+                        Some(result) => {
+                            edit::remove(&mut session.db, &result.entry.uuid, false)?;
+                            session.save()?;
+                            Ok(())
+                        }
+                        None => {
+                            println!("{}", docker_credential::NOT_FOUND);
+                            process::exit(1);
+                        }
+                    }
+                }
+                "list" => {
+                    let session = open_session(args, config)?;
+                    let registries = match listing::find_group(&session.db.root, group) {
+                        Ok(group) => docker_credential::list(group),
+                        Err(_) => Default::default(),
+                    };
+                    print_structured(&registries, &OutputFormat::Json)
+                }
+                other => Err(anyhow!("Unknown docker credential action: {}", other)),
+            }
+        }
+    }
+}
+
+/// Docker runs credential helpers as `docker-credential-<name> <action>`;
+/// when installed under such a name, behave like `kpasscli docker-credential`.
+fn helper_args(mut argv: Vec<OsString>) -> Vec<OsString> {
+    let invoked_as_helper = argv.first().is_some_and(|arg0| {
+        Path::new(arg0)
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with("docker-credential-"))
+    });
+    if invoked_as_helper {
+        argv.insert(1, OsString::from("docker-credential"));
+    }
+    argv
+}
+
+/// Refuses generator options given to `add`/`edit` without `--generate`,
+/// which would otherwise be ignored.
+fn check_generator_args(generate: bool, gen_args: &GeneratorArgs) -> Result<()> {
+    let given = gen_args.profile.is_some()
+        || gen_args.length.is_some()
+        || gen_args.no_lower
+        || gen_args.no_upper
+        || gen_args.no_digits
+        || gen_args.no_symbols
+        || gen_args.exclude_similar
+        || gen_args.min_per_class.is_some()
+        || gen_args.passphrase
+        || gen_args.words.is_some()
+        || gen_args.separator.is_some();
+    if given && !generate {
+        return Err(anyhow!("Generator options require --generate"));
+    }
+    Ok(())
+}
+
+fn generate_password(gen_args: &GeneratorArgs, config: &Config) -> Result<String> {
+    let profile = GeneratorProfile::resolve(gen_args, config)?;
+    generator::generate(&profile)
+}
+
+fn search_options(args: &Args) -> SearchOptions {
+    SearchOptions {
+        case_sensitive: args.case_sensitive,
+        exact_match: args.exact_match,
+        fields: args.search_in.clone(),
+        regex: args.regex,
+        glob: args.glob,
+        fuzzy: args.fuzzy,
+        tags: args.tags.clone(),
+    }
+}
+
+fn find_single(db: &Database, query: &str, args: &Args) -> Result<SearchResult> {
+    let finder = Finder::new(db, search_options(args));
+    select_single(finder.find(query)?, args)
+}
+
+/// Finds the entry for a registry in `group`, by URL field or by title.
+fn find_registry(
+    db: &Database,
+    group: &str,
+    server_url: &str,
+    args: &Args,
+) -> Result<Option<SearchResult>> {
+    let finder = Finder::new(db, search_options(args));
+    let query = docker_credential::registry_query(server_url)?;
+    if let Some(result) = finder
+        .find_by_url(&query)
+        .into_iter()
+        .find(|result| docker_credential::in_group(group, result))
+    {
+        return Ok(Some(result));
+    }
+    let path = docker_credential::entry_path(group, server_url)?;
+    Ok(finder.find(&path)?.pop())
+}
+
+/// Picks one of the results, best match first: the one chosen with
+/// --first or --index, or interactively when stderr is a terminal.
+fn select_single(mut results: Vec<SearchResult>, args: &Args) -> Result<SearchResult> {
+    if results.is_empty() {
+        return Err(NotFound("no items found".to_string()).into());
+    }
+
+    if let Some(n) = args.index {
+        if n == 0 || n > results.len() {
+            return Err(anyhow!(
+                "--index {} is out of range, {} items found",
+                n,
+                results.len()
+            ));
+        }
+        return Ok(results.remove(n - 1));
+    }
+
+    if results.len() > 1 && !args.first {
+        if picker::available() {
+            let index = picker::pick(&results)?;
+            return Ok(results.remove(index));
+        }
+        for (i, result) in results.iter().enumerate() {
+            eprintln!("{:>3}) {}", i + 1, picker::label(result));
+        }
+        return Err(anyhow!(
+            "multiple items found (use --first or --index N to pick one)"
+        ));
+    }
+
+    Ok(results.remove(0))
+}
+
 fn clear_clipboard() -> Result<()> {
     #[cfg(target_os = "linux")]
     {
@@ -1,32 +1,282 @@
+//! One-time passwords from `otpauth://` URIs: TOTP and HOTP with the
+//! `algorithm`, `digits`, `period` and `counter` parameters, and the
+//! 5-character Steam Guard codes (`encoder=steam` or `otpauth://steam/`).
+//!
+//! The TOTP settings other clients store are read as well: the
+//! `TimeOtp-*` fields of KeePass 2.47+, the `key=...&step=...` string of
+//! the KeeOtp plugin in `otp` and the `TOTP Seed`/`TOTP Settings` pair.
+
 use anyhow::{anyhow, Result};
+use base64::Engine;
+use keepass::db::Entry;
+use std::time::{SystemTime, UNIX_EPOCH};
 use totp_rs::{Algorithm, Secret, TOTP};
 use url::Url;
 
-pub fn generate_totp(otp_url: &str) -> Result<String> {
-    let url = Url::parse(otp_url).map_err(|e| anyhow!("Failed to parse TOTP URL: {}", e))?;
+const DEFAULT_DIGITS: usize = 6;
+const DEFAULT_PERIOD: u64 = 30;
+const STEAM_DIGITS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// Time-based; the code changes every `period` seconds.
+    Totp { period: u64 },
+    /// Counter-based; the counter must be incremented after each code.
+    Hotp { counter: u64 },
+}
+
+#[derive(Debug)]
+pub struct Otp {
+    pub kind: Kind,
+    algorithm: Algorithm,
+    digits: usize,
+    secret: Vec<u8>,
+}
+
+impl Otp {
+    pub fn from_url(otp_url: &str) -> Result<Self> {
+        let url = Url::parse(otp_url).map_err(|e| anyhow!("Failed to parse OTP URL: {}", e))?;
+        if url.scheme() != "otpauth" {
+            return Err(anyhow!("Invalid scheme"));
+        }
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.to_string())
+        };
+        let number = |name: &str| parse_number(name, param(name).as_deref());
+
+        let secret = param("secret").ok_or_else(|| anyhow!("No secret found in URL"))?;
+        let steam = url.host_str() == Some("steam")
+            || param("encoder").is_some_and(|e| e.eq_ignore_ascii_case("steam"));
+        let kind = match url.host_str() {
+            Some("totp") | Some("steam") => Kind::Totp {
+                period: number("period")?.unwrap_or(DEFAULT_PERIOD),
+            },
+            Some("hotp") => Kind::Hotp {
+                counter: number("counter")?
+                    .ok_or_else(|| anyhow!("No counter found in HOTP URL"))?,
+            },
+            _ => return Err(anyhow!("Only TOTP and HOTP are supported")),
+        };
+        if kind == (Kind::Totp { period: 0 }) {
+            return Err(anyhow!("Invalid period 0 in OTP URL"));
+        }
+
+        let (algorithm, digits) = if steam {
+            (Algorithm::Steam, STEAM_DIGITS)
+        } else {
+            let algorithm = param("algorithm").unwrap_or_else(|| "SHA1".to_string());
+            (
+                parse_algorithm(&algorithm)?,
+                parse_digits(number("digits")?)?,
+            )
+        };
+
+        Ok(Self {
+            kind,
+            algorithm,
+            digits,
+            secret: decode_secret(&secret)?,
+        })
+    }
+
+    /// The OTP configuration of `entry`, whichever client wrote it; `None`
+    /// if the entry has none.
+    pub fn from_entry(entry: &Entry) -> Result<Option<Self>> {
+        if let Some(otp) = entry.get("otp").map(str::trim).filter(|v| !v.is_empty()) {
+            return if otp.starts_with("otpauth:") {
+                Self::from_url(otp).map(Some)
+            } else {
+                Self::from_keeotp(otp).map(Some)
+            };
+        }
+        if let Some(otp) = Self::from_keepass(entry)? {
+            return Ok(Some(otp));
+        }
+        entry
+            .get("TOTP Seed")
+            .map(|seed| Self::from_seed(seed, entry.get("TOTP Settings")))
+            .transpose()
+    }
+
+    fn totp(secret: Vec<u8>, period: u64, algorithm: Algorithm, digits: usize) -> Result<Self> {
+        if period == 0 {
+            return Err(anyhow!("Invalid TOTP period 0"));
+        }
+        Ok(Self {
+            kind: Kind::Totp { period },
+            algorithm,
+            digits,
+            secret,
+        })
+    }
+
+    /// KeeOtp: `key=SECRET&step=30&size=6&otpHashMode=Sha256`.
+    fn from_keeotp(value: &str) -> Result<Self> {
+        let pairs: Vec<(String, String)> = url::form_urlencoded::parse(value.as_bytes())
+            .map(|(k, v)| (k.to_ascii_lowercase(), v.to_string()))
+            .collect();
+        let param = |name: &str| {
+            pairs
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.as_str())
+        };
+        let secret = param("key").ok_or_else(|| anyhow!("Unsupported otp field format"))?;
+        if param("type").is_some_and(|t| !t.eq_ignore_ascii_case("totp")) {
+            return Err(anyhow!("Only TOTP is supported in KeeOtp settings"));
+        }
+        Self::totp(
+            decode_secret(secret)?,
+            parse_number("step", param("step"))?.unwrap_or(DEFAULT_PERIOD),
+            parse_algorithm(param("otphashmode").unwrap_or("SHA1"))?,
+            parse_digits(parse_number("size", param("size"))?)?,
+        )
+    }
+
+    /// KeePass 2.47+: `TimeOtp-Secret-Base32` (or `-Hex`, `-Base64`, plain
+    /// `TimeOtp-Secret`), `TimeOtp-Length`, `TimeOtp-Period` and
+    /// `TimeOtp-Algorithm`.
+    fn from_keepass(entry: &Entry) -> Result<Option<Self>> {
+        let secret = if let Some(secret) = entry.get("TimeOtp-Secret-Base32") {
+            decode_secret(secret)?
+        } else if let Some(secret) = entry.get("TimeOtp-Secret-Hex") {
+            decode_hex(secret)?
+        } else if let Some(secret) = entry.get("TimeOtp-Secret-Base64") {
+            base64::engine::general_purpose::STANDARD
+                .decode(secret.trim())
+                .map_err(|_| anyhow!("Invalid base64 secret in TimeOtp-Secret-Base64"))?
+        } else if let Some(secret) = entry.get("TimeOtp-Secret") {
+            secret.as_bytes().to_vec()
+        } else {
+            return Ok(None);
+        };
+        Self::totp(
+            secret,
+            parse_number("TimeOtp-Period", entry.get("TimeOtp-Period"))?.unwrap_or(DEFAULT_PERIOD),
+            parse_algorithm(entry.get("TimeOtp-Algorithm").unwrap_or("SHA1"))?,
+            parse_digits(parse_number("TimeOtp-Length", entry.get("TimeOtp-Length"))?)?,
+        )
+        .map(Some)
+    }
+
+    /// `TOTP Seed` with `TOTP Settings` as `PERIOD;DIGITS`, where the digits
+    /// are `S` for Steam.
+    fn from_seed(seed: &str, settings: Option<&str>) -> Result<Self> {
+        let (period, digits) = settings
+            .and_then(|s| s.trim().split_once(';'))
+            .map_or((None, None), |(p, d)| (Some(p), Some(d)));
+        let period = parse_number("TOTP Settings period", period)?.unwrap_or(DEFAULT_PERIOD);
+        let (algorithm, digits) = if digits.is_some_and(|d| d.trim() == "S") {
+            (Algorithm::Steam, STEAM_DIGITS)
+        } else {
+            (
+                Algorithm::SHA1,
+                parse_digits(parse_number("TOTP Settings digits", digits)?)?,
+            )
+        };
+        Self::totp(decode_secret(seed)?, period, algorithm, digits)
+    }
 
-    if url.scheme() != "otpauth" {
-        return Err(anyhow!("Invalid scheme"));
+    /// The current code: for HOTP the code of the stored counter.
+    pub fn generate(&self) -> Result<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| anyhow!("Failed to generate TOTP: {}", e))?;
+        Ok(self.generate_at(now.as_secs()))
     }
 
-    if url.host_str() != Some("totp") {
-        return Err(anyhow!("Only TOTP is supported"));
+    fn generate_at(&self, time: u64) -> String {
+        // HOTP is TOTP with a one-second step and the counter as time
+        let (step, time) = match self.kind {
+            Kind::Totp { period } => (period, time),
+            Kind::Hotp { counter } => (1, counter),
+        };
+        TOTP::new_unchecked(self.algorithm, self.digits, 1, step, self.secret.clone())
+            .generate(time)
     }
+}
 
-    let secret_str = url
+/// The current code of an `otpauth://` URL.
+#[cfg(test)]
+pub fn generate_totp(otp_url: &str) -> Result<String> {
+    Otp::from_url(otp_url)?.generate()
+}
+
+/// Returns `otp_url` with its HOTP counter incremented, other parameters
+/// unchanged.
+pub fn increment_counter(otp_url: &str) -> Result<String> {
+    let mut url = Url::parse(otp_url).map_err(|e| anyhow!("Failed to parse OTP URL: {}", e))?;
+    let Kind::Hotp { counter } = Otp::from_url(otp_url)?.kind else {
+        return Err(anyhow!("Not a HOTP URL"));
+    };
+    let pairs: Vec<(String, String)> = url
         .query_pairs()
-        .find(|(k, _)| k == "secret")
-        .map(|(_, v)| v.to_string())
-        .ok_or_else(|| anyhow!("No secret found in URL"))?;
+        .map(|(k, v)| {
+            if k.eq_ignore_ascii_case("counter") {
+                (k.to_string(), (counter + 1).to_string())
+            } else {
+                (k.to_string(), v.to_string())
+            }
+        })
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    Ok(url.to_string())
+}
+
+fn parse_number(name: &str, value: Option<&str>) -> Result<Option<u64>> {
+    value
+        .map(|v| {
+            v.trim()
+                .parse()
+                .map_err(|_| anyhow!("Invalid OTP {} '{}'", name, v))
+        })
+        .transpose()
+}
+
+/// Accepts `SHA256`, `sha-256` and KeePass' `HMAC-SHA-256`.
+fn parse_algorithm(name: &str) -> Result<Algorithm> {
+    let normalized = name.to_ascii_uppercase().replace('-', "");
+    match normalized.strip_prefix("HMAC").unwrap_or(&normalized) {
+        "SHA1" => Ok(Algorithm::SHA1),
+        "SHA256" => Ok(Algorithm::SHA256),
+        "SHA512" => Ok(Algorithm::SHA512),
+        _ => Err(anyhow!("Unsupported OTP algorithm '{}'", name)),
+    }
+}
+
+fn parse_digits(digits: Option<u64>) -> Result<usize> {
+    match digits {
+        None => Ok(DEFAULT_DIGITS),
+        Some(d @ 6..=8) => Ok(d as usize),
+        Some(d) => Err(anyhow!("Unsupported number of OTP digits {}", d)),
+    }
+}
 
+fn decode_secret(secret: &str) -> Result<Vec<u8>> {
     // Remove spaces and padding
-    let clean_secret = secret_str.replace(' ', "").replace('=', "");
+    let clean_secret = secret.replace([' ', '='], "").to_ascii_uppercase();
+    Secret::Encoded(clean_secret)
+        .to_bytes()
+        .map_err(|_| anyhow!("Invalid base32 OTP secret"))
+}
 
-    let secret = Secret::Encoded(clean_secret);
-    let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, secret.to_bytes().unwrap())
-        .map_err(|e| anyhow!("Failed to create TOTP instance: {}", e))?;
-    totp.generate_current()
-        .map_err(|e| anyhow!("Failed to generate TOTP: {}", e))
+fn decode_hex(secret: &str) -> Result<Vec<u8>> {
+    let secret: Vec<u8> = secret
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    secret
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid hex secret in TimeOtp-Secret-Hex"))
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -47,4 +297,113 @@ mod tests {
         assert_eq!(token.len(), 6);
         assert!(token.chars().all(char::is_numeric));
     }
+
+    #[test]
+    fn test_rfc_vectors() {
+        // RFC 6238 appendix B: the ASCII seeds "1234567890" repeated to the
+        // length of the hash, at T = 59 seconds
+        let sha1 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let sha256 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+        let sha512 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA";
+        let code = |url: String| Otp::from_url(&url).unwrap().generate_at(59);
+        assert_eq!(
+            code(format!("otpauth://totp/x?secret={}&digits=8", sha1)),
+            "94287082"
+        );
+        assert_eq!(
+            code(format!(
+                "otpauth://totp/x?secret={}&digits=8&algorithm=SHA256",
+                sha256
+            )),
+            "46119246"
+        );
+        assert_eq!(
+            code(format!(
+                "otpauth://totp/x?secret={}&digits=8&algorithm=sha512",
+                sha512
+            )),
+            "90693936"
+        );
+        // Period 60: same code as step 0 of 30 seconds
+        assert_eq!(
+            code(format!("otpauth://totp/x?secret={}&period=60", sha1)),
+            Otp::from_url(&format!("otpauth://totp/x?secret={}", sha1))
+                .unwrap()
+                .generate_at(29)
+        );
+
+        // RFC 4226 appendix D: counters 0 and 1
+        let hotp = format!("otpauth://hotp/x?secret={}&counter=0", sha1);
+        assert_eq!(code(hotp.clone()), "755224");
+        let next = increment_counter(&hotp).unwrap();
+        assert_eq!(next, format!("otpauth://hotp/x?secret={}&counter=1", sha1));
+        assert_eq!(code(next), "287082");
+
+        let steam = code(format!("otpauth://totp/x?secret={}&encoder=steam", sha1));
+        assert_eq!(steam.len(), 5);
+        assert!(steam.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        assert!(Otp::from_url("otpauth://totp/x?secret=JBSWY3DP&algorithm=MD5").is_err());
+        assert!(Otp::from_url("otpauth://totp/x?secret=JBSWY3DP&digits=12").is_err());
+        assert!(Otp::from_url("otpauth://hotp/x?secret=JBSWY3DP").is_err());
+    }
+
+    #[test]
+    fn test_from_entry() {
+        let sha256 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+        let entry = |fields: &[(&str, &str)]| {
+            let mut entry = Entry::new();
+            for (name, value) in fields {
+                entry.fields.insert(
+                    name.to_string(),
+                    keepass::db::Value::Unprotected(value.to_string()),
+                );
+            }
+            entry
+        };
+        let code = |fields: &[(&str, &str)]| {
+            Otp::from_entry(&entry(fields))
+                .unwrap()
+                .map(|otp| otp.generate_at(59))
+        };
+        let expected = Some("46119246".to_string());
+
+        let url = format!(
+            "otpauth://totp/x?secret={}&digits=8&algorithm=SHA256",
+            sha256
+        );
+        assert_eq!(code(&[("otp", &url)]), expected);
+        let keeotp = format!("key={}&size=8&otpHashMode=Sha256", sha256);
+        assert_eq!(code(&[("otp", &keeotp)]), expected);
+        assert_eq!(
+            code(&[
+                ("TimeOtp-Secret-Base32", sha256),
+                ("TimeOtp-Length", "8"),
+                ("TimeOtp-Algorithm", "HMAC-SHA-256"),
+            ]),
+            expected
+        );
+        let hex = "3132333435363738393031323334353637383930313233343536373839303132";
+        assert_eq!(
+            code(&[
+                ("TimeOtp-Secret-Hex", hex),
+                ("TimeOtp-Length", "8"),
+                ("TimeOtp-Algorithm", "HMAC-SHA-256"),
+                ("TimeOtp-Period", "30"),
+            ]),
+            expected
+        );
+
+        let sha1 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(
+            code(&[("TOTP Seed", sha1), ("TOTP Settings", "30;8")]),
+            Some("94287082".to_string())
+        );
+        assert_eq!(code(&[("TOTP Seed", sha1)]), Some("287082".to_string()));
+        let steam = code(&[("TOTP Seed", sha1), ("TOTP Settings", "30;S")]).unwrap();
+        assert_eq!(steam.len(), 5);
+
+        assert_eq!(code(&[("Title", "none")]), None);
+        assert!(Otp::from_entry(&entry(&[("otp", "not a secret")])).is_err());
+    }
 }
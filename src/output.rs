@@ -1,11 +1,22 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use arboard::Clipboard;
-use keepass::db::Entry;
+use keepass::db::{Entry, Value};
+use serde::Serialize;
+use std::collections::BTreeMap;
 
 use crate::config::Config;
+use crate::search::SearchResult;
 use std::io::Write;
 use std::process::{Command, Stdio};
 
+/// Timestamp format used in structured output (KeePass times are UTC).
+pub const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Replaces protected values in structured output unless they are revealed.
+const MASK: &str = "********";
+
+const STANDARD_FIELDS: [&str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
 pub enum OutputType {
     Stdout,
     Clipboard,
@@ -21,6 +32,13 @@ impl OutputType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
+}
+
 pub struct Handler {
     output_type: OutputType,
     clipboard_timeout: Option<u64>,
@@ -187,6 +205,9 @@ pub fn show_all_fields(entry: &Entry) {
     if let Some(notes) = entry.get("Notes") {
         println!("Notes: {}", notes);
     }
+    if !entry.tags.is_empty() {
+        println!("Tags: {}", entry.tags.join(", "));
+    }
 
     // Custom fields
     for key in entry.fields.keys() {
@@ -197,22 +218,202 @@ pub fn show_all_fields(entry: &Entry) {
 
     println!("----------------------------------------");
     println!("Metadata:");
-    // Times
-    // keepass crate might have different API for times.
-    // Let's assume get_times() exists or similar.
-    // If not, we will fix it.
-    // entry.times is public in keepass crate usually.
+    println!("UUID: {}", entry.uuid);
+    let times = &entry.times;
+    let format = |t: Option<&chrono::NaiveDateTime>| {
+        t.map_or("-".to_string(), |t| t.format(TIME_FORMAT).to_string())
+    };
+    println!("Created: {}", format(times.creation.as_ref()));
+    println!("Modified: {}", format(times.last_modification.as_ref()));
+    println!("Accessed: {}", format(times.last_access.as_ref()));
+    match crate::expiry::expiry(entry) {
+        Some(t) if crate::expiry::is_expired(entry) => {
+            println!("Expires: {} (expired)", format(Some(&t)))
+        }
+        Some(t) => println!("Expires: {}", format(Some(&t))),
+        None => println!("Expires: never"),
+    }
+}
+
+/// Machine-readable view of an entry for `--format json|yaml`.
+#[derive(Debug, Serialize)]
+pub struct EntryRecord {
+    pub path: String,
+    pub uuid: String,
+    pub title: String,
+    pub username: String,
+    pub password: String,
+    pub url: String,
+    pub notes: String,
+    pub custom_fields: BTreeMap<String, String>,
+    pub protected_fields: Vec<String>,
+    pub tags: Vec<String>,
+    pub times: TimesRecord,
+    pub expires: bool,
+    pub expired: bool,
+    /// Fields a search matched in.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matched: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimesRecord {
+    pub created: Option<String>,
+    pub modified: Option<String>,
+    pub accessed: Option<String>,
+    pub location_changed: Option<String>,
+    pub expiry: Option<String>,
+}
+
+impl EntryRecord {
+    pub fn new(path: &str, entry: &Entry, reveal: bool) -> Self {
+        let value = |name: &str| -> String {
+            match entry.fields.get(name) {
+                Some(Value::Protected(_)) if !reveal => MASK.to_string(),
+                _ => entry.get(name).unwrap_or_default().to_string(),
+            }
+        };
+
+        let mut protected_fields: Vec<String> = entry
+            .fields
+            .iter()
+            .filter(|(_, v)| matches!(v, Value::Protected(_)))
+            .map(|(k, _)| k.clone())
+            .collect();
+        protected_fields.sort();
+
+        let custom_fields = entry
+            .fields
+            .keys()
+            .filter(|k| !STANDARD_FIELDS.contains(&k.as_str()))
+            .filter(|k| entry.get(k).is_some())
+            .map(|k| (k.clone(), value(k)))
+            .collect();
+
+        let format =
+            |t: Option<&chrono::NaiveDateTime>| t.map(|t| t.format(TIME_FORMAT).to_string());
+        let times = &entry.times;
+        let expires = times.expires.unwrap_or(false);
+        let expired = crate::expiry::is_expired(entry);
 
-    /*
-    let times = entry.get_times();
-    if let Some(t) = times.get_creation() {
-        println!("Created: {}", t);
+        Self {
+            path: path.to_string(),
+            uuid: entry.uuid.to_string(),
+            title: value("Title"),
+            username: value("UserName"),
+            password: value("Password"),
+            url: value("URL"),
+            notes: value("Notes"),
+            custom_fields,
+            protected_fields,
+            tags: entry.tags.clone(),
+            times: TimesRecord {
+                created: format(times.creation.as_ref()),
+                modified: format(times.last_modification.as_ref()),
+                accessed: format(times.last_access.as_ref()),
+                location_changed: format(times.location_changed.as_ref()),
+                expiry: if expires {
+                    format(times.expiry.as_ref())
+                } else {
+                    None
+                },
+            },
+            expires,
+            expired,
+            matched: Vec::new(),
+        }
+    }
+}
+
+/// Prints `value` as JSON or YAML; `Text` is handled by the callers.
+pub fn print_structured<T: Serialize>(value: &T, format: &OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        OutputFormat::Text => return Err(anyhow!("Text is not a structured format")),
+    }
+    Ok(())
+}
+
+/// Prints one entry as an object, or several matches as an array.
+pub fn print_results(results: &[SearchResult], format: &OutputFormat, reveal: bool) -> Result<()> {
+    let records: Vec<EntryRecord> = results
+        .iter()
+        .map(|r| EntryRecord {
+            matched: r.matched.clone(),
+            ..EntryRecord::new(&r.path, &r.entry, reveal)
+        })
+        .collect();
+    match records.as_slice() {
+        [record] => print_structured(record, format),
+        _ => print_structured(&records, format),
+    }
+}
+
+/// Writes `content` to `path`, readable and writable by the owner only.
+pub fn write_private(path: &str, content: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
     }
-    if let Some(t) = times.get_last_modification() {
-        println!("Modified: {}", t);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create output file: {}", path))?;
+    #[cfg(unix)]
+    {
+        // mode() only applies to newly created files
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
     }
-    if let Some(t) = times.get_last_access() {
-        println!("Accessed: {}", t);
+    file.write_all(content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::{protected, unprotected};
+
+    fn entry() -> Entry {
+        let mut entry = Entry::new();
+        entry
+            .fields
+            .insert("Title".to_string(), unprotected("db01"));
+        entry
+            .fields
+            .insert("UserName".to_string(), unprotected("admin"));
+        entry
+            .fields
+            .insert("Password".to_string(), protected("s3cret"));
+        entry.fields.insert("Token".to_string(), protected("abc"));
+        entry.fields.insert("Port".to_string(), unprotected("5432"));
+        entry
+    }
+
+    #[test]
+    fn test_entry_record_masks_protected_values() {
+        let record = EntryRecord::new("/Servers/db01", &entry(), false);
+        let json: serde_json::Value = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["path"], "/Servers/db01");
+        assert_eq!(json["username"], "admin");
+        assert_eq!(json["password"], MASK);
+        assert_eq!(json["custom_fields"]["Token"], MASK);
+        assert_eq!(json["custom_fields"]["Port"], "5432");
+        assert_eq!(
+            json["protected_fields"],
+            serde_json::json!(["Password", "Token"])
+        );
+        assert!(json.get("matched").is_none());
+
+        let yaml = serde_yaml::to_string(&record).unwrap();
+        assert!(yaml.contains("password: '********'"));
+        assert!(!yaml.contains("s3cret"));
+
+        let record = EntryRecord::new("/Servers/db01", &entry(), true);
+        assert_eq!(record.password, "s3cret");
+        assert_eq!(record.custom_fields["Token"], "abc");
     }
-    */
 }
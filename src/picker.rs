@@ -0,0 +1,55 @@
+//! Interactive choice between several search results. The list and prompt
+//! go to stderr and the answer is read from the terminal, so stdout stays
+//! free for the selected value.
+
+use anyhow::{anyhow, Context, Result};
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+
+use crate::search::SearchResult;
+
+/// Whether a user can be asked to pick an entry.
+pub fn available() -> bool {
+    std::io::stderr().is_terminal()
+}
+
+/// Label of a result in lists: its path, and the matched fields unless the
+/// title matched.
+pub fn label(result: &SearchResult) -> String {
+    if result.matched.iter().all(|field| field == "Title") {
+        result.path.clone()
+    } else {
+        format!("{} (matched: {})", result.path, result.matched.join(", "))
+    }
+}
+
+/// Lists `results` and asks for a number; returns the index of the chosen
+/// result.
+pub fn pick(results: &[SearchResult]) -> Result<usize> {
+    let mut stderr = std::io::stderr();
+    for (i, result) in results.iter().enumerate() {
+        writeln!(stderr, "{:>3}) {}", i + 1, label(result))?;
+    }
+    let mut input = open_terminal()?;
+    loop {
+        write!(stderr, "Select entry [1-{}]: ", results.len())?;
+        stderr.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            return Err(anyhow!("No entry selected"));
+        }
+        match line.trim().parse::<usize>() {
+            Ok(n) if (1..=results.len()).contains(&n) => return Ok(n - 1),
+            _ => writeln!(stderr, "Enter a number between 1 and {}", results.len())?,
+        }
+    }
+}
+
+/// Reads from the controlling terminal, as stdin may carry data.
+fn open_terminal() -> Result<Box<dyn BufRead>> {
+    #[cfg(unix)]
+    let path = "/dev/tty";
+    #[cfg(windows)]
+    let path = "CONIN$";
+    let tty = std::fs::File::open(path).context("Failed to open the terminal")?;
+    Ok(Box::new(BufReader::new(tty)))
+}
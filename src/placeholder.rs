@@ -0,0 +1,377 @@
+//! KeePass field references such as `{REF:P@I:46C9B1FFBD4ABC4BBB260C6190BAD20C}`
+//! and placeholders such as `{USERNAME}`, `{S:Custom}` or `{URL:HOST}` in
+//! field values. Unknown placeholders, and references that cannot be
+//! resolved, are left as they are.
+
+use anyhow::{anyhow, Result};
+use keepass::db::{Entry, History, Value};
+use keepass::Database;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::search::{parse_entry_url, parse_uuid, Finder, SearchOptions, SearchResult};
+
+const STANDARD_FIELDS: [&str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
+/// How many references may be followed from one field value.
+const MAX_DEPTH: usize = 10;
+
+/// The fields of an entry that get resolved: only those that are output.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Fields {
+    #[default]
+    All,
+    /// Field names, matched ignoring case.
+    Only(Vec<String>),
+}
+
+impl Fields {
+    pub fn only(names: &[&str]) -> Self {
+        Fields::Only(names.iter().map(|n| n.to_string()).collect())
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        match self {
+            Fields::All => true,
+            Fields::Only(names) => names.iter().any(|n| n.eq_ignore_ascii_case(name)),
+        }
+    }
+}
+
+pub struct Resolver<'a> {
+    db: &'a Database,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// Returns a copy of `entry` with references and placeholders replaced
+    /// in `fields`, and in its previous versions if `history` is set.
+    pub fn resolve_entry(&self, entry: &Entry, fields: &Fields, history: bool) -> Entry {
+        let mut resolved = entry.clone();
+        for (name, value) in &entry.fields {
+            if !fields.contains(name) || !value.contains('{') {
+                continue;
+            }
+            let text = self.resolve(entry, value, &mut vec![(entry.uuid, name.clone())]);
+            let value = match value {
+                Value::Protected(_) => crate::edit::protected(&text),
+                _ => crate::edit::unprotected(&text),
+            };
+            resolved.fields.insert(name.clone(), value);
+        }
+
+        if let (true, Some(versions)) = (history, &entry.history) {
+            let mut resolved_versions = History::default();
+            // add_entry inserts at the front
+            for version in versions.get_entries().iter().rev() {
+                resolved_versions.add_entry(self.resolve_entry(version, fields, false));
+            }
+            resolved.history = Some(resolved_versions);
+        }
+        resolved
+    }
+
+    /// Resolves `fields` in the entries of search results.
+    pub fn resolve_results(
+        &self,
+        results: Vec<SearchResult>,
+        fields: &Fields,
+        history: bool,
+    ) -> Vec<SearchResult> {
+        results
+            .into_iter()
+            .map(|result| SearchResult {
+                entry: self.resolve_entry(&result.entry, fields, history),
+                ..result
+            })
+            .collect()
+    }
+
+    /// The resolved value of field `name`. `stack` holds the fields being
+    /// resolved, to detect cycles.
+    fn field_value(
+        &self,
+        entry: &Entry,
+        name: &str,
+        stack: &mut Vec<(Uuid, String)>,
+    ) -> Result<String> {
+        let key = (entry.uuid, name.to_string());
+        if stack.contains(&key) {
+            return Err(anyhow!(
+                "Circular reference in field {} of '{}'",
+                name,
+                entry.get_title().unwrap_or_default()
+            ));
+        }
+        if stack.len() >= MAX_DEPTH {
+            return Err(anyhow!(
+                "References nested deeper than {} levels",
+                MAX_DEPTH
+            ));
+        }
+
+        stack.push(key);
+        let raw = entry.get(name).unwrap_or_default();
+        let value = self.resolve(entry, raw, stack);
+        stack.pop();
+        Ok(value)
+    }
+
+    /// Replaces the placeholders in `text`. Like KeePass, references that
+    /// cannot be resolved stay as written; a warning names the reason.
+    fn resolve(&self, entry: &Entry, text: &str, stack: &mut Vec<(Uuid, String)>) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            let candidate = &rest[start..];
+            let Some(end) = candidate.find('}') else {
+                break;
+            };
+            output.push_str(&rest[..start]);
+            let token = &candidate[1..end];
+            match self.expand(entry, token, stack) {
+                Ok(Some(value)) => output.push_str(&value),
+                Ok(None) => output.push_str(&candidate[..=end]),
+                Err(e) => {
+                    eprintln!(
+                        "Warning: {:#} (in '{}', left as written)",
+                        e,
+                        entry.get_title().unwrap_or_default()
+                    );
+                    output.push_str(&candidate[..=end]);
+                }
+            }
+            rest = &candidate[end + 1..];
+        }
+        output.push_str(rest);
+        output
+    }
+
+    /// Expands one `{token}`; `None` for unknown placeholders.
+    fn expand(
+        &self,
+        entry: &Entry,
+        token: &str,
+        stack: &mut Vec<(Uuid, String)>,
+    ) -> Result<Option<String>> {
+        let upper = token.to_ascii_uppercase();
+        if let Some(reference) = upper.strip_prefix("REF:") {
+            // Keep the search text as written; only the codes are case-insensitive
+            let text = &token[token.find(':').map_or(0, |i| i + 1)..];
+            return self.expand_reference(reference, text, stack).map(Some);
+        }
+        if let Some(name) = token.get(..2).filter(|p| p.eq_ignore_ascii_case("S:")) {
+            return self
+                .field_value(entry, &token[name.len()..], stack)
+                .map(Some);
+        }
+        if let Some(part) = upper.strip_prefix("URL:") {
+            let url = self.field_value(entry, "URL", stack)?;
+            return Ok(url_part(&url, part));
+        }
+        let value = match upper.as_str() {
+            "TITLE" => self.field_value(entry, "Title", stack)?,
+            "USERNAME" => self.field_value(entry, "UserName", stack)?,
+            "PASSWORD" => self.field_value(entry, "Password", stack)?,
+            "URL" => self.field_value(entry, "URL", stack)?,
+            "NOTES" => self.field_value(entry, "Notes", stack)?,
+            "UUID" => uuid_hex(&entry.uuid),
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+
+    /// Expands `{REF:<wanted>@<search in>:<text>}`.
+    fn expand_reference(
+        &self,
+        reference: &str,
+        text: &str,
+        stack: &mut Vec<(Uuid, String)>,
+    ) -> Result<String> {
+        let invalid = || anyhow!("Invalid reference: {{REF:{}}}", reference);
+        let (codes, _) = reference.split_once(':').ok_or_else(invalid)?;
+        let (wanted, search_in) = codes.split_once('@').ok_or_else(invalid)?;
+        let search_text = text.split_once(':').map(|(_, t)| t).ok_or_else(invalid)?;
+
+        let target = self
+            .find_target(search_in, search_text)?
+            .ok_or_else(|| anyhow!("Reference not found: {{REF:{}}}", reference))?;
+        match wanted {
+            "I" => Ok(uuid_hex(&target.uuid)),
+            code => {
+                let name = field_name(code).ok_or_else(invalid)?;
+                self.field_value(&target, name, stack)
+            }
+        }
+    }
+
+    /// Finds the entry a reference points to: by UUID, or the first entry
+    /// whose field equals (or else contains) the text, ignoring case.
+    fn find_target(&self, search_in: &str, text: &str) -> Result<Option<Entry>> {
+        let finder = Finder::new(self.db, SearchOptions::default());
+        if search_in == "I" {
+            let uuid = parse_uuid(text)?;
+            let mut found = None;
+            finder.for_each_entry(&mut |_, entry| {
+                if found.is_none() && entry.uuid == uuid {
+                    found = Some(entry.clone());
+                }
+            });
+            return Ok(found);
+        }
+
+        let names: Vec<&str> = match search_in {
+            "O" => Vec::new(),
+            code => {
+                vec![field_name(code).ok_or_else(|| anyhow!("Invalid reference field: {}", code))?]
+            }
+        };
+        let text = text.to_lowercase();
+        let (mut exact, mut partial) = (None, None);
+        finder.for_each_entry(&mut |_, entry| {
+            if exact.is_some() {
+                return;
+            }
+            let values: Vec<String> = if names.is_empty() {
+                // Other fields: every custom field
+                entry
+                    .fields
+                    .keys()
+                    .filter(|k| !STANDARD_FIELDS.contains(&k.as_str()))
+                    .filter_map(|k| entry.get(k))
+                    .map(str::to_lowercase)
+                    .collect()
+            } else {
+                names
+                    .iter()
+                    .filter_map(|n| entry.get(n))
+                    .map(str::to_lowercase)
+                    .collect()
+            };
+            if values.contains(&text) {
+                exact = Some(entry.clone());
+            } else if partial.is_none() && values.iter().any(|v| v.contains(&text)) {
+                partial = Some(entry.clone());
+            }
+        });
+        Ok(exact.or(partial))
+    }
+}
+
+fn field_name(code: &str) -> Option<&'static str> {
+    match code {
+        "T" => Some("Title"),
+        "U" => Some("UserName"),
+        "P" => Some("Password"),
+        "A" => Some("URL"),
+        "N" => Some("Notes"),
+        _ => None,
+    }
+}
+
+/// KeePass writes UUIDs in references as 32 upper-case hex digits.
+fn uuid_hex(uuid: &Uuid) -> String {
+    uuid.simple().to_string().to_uppercase()
+}
+
+fn url_part(value: &str, part: &str) -> Option<String> {
+    let url = parse_entry_url(value);
+    let value = match part {
+        "RMVSCM" => value
+            .split_once("://")
+            .map_or(value, |(_, rest)| rest)
+            .to_string(),
+        "SCM" => url?.scheme().to_string(),
+        "HOST" => url?.host_str().unwrap_or_default().to_string(),
+        "PORT" => url?
+            .port_or_known_default()
+            .map(|p| p.to_string())
+            .unwrap_or_default(),
+        "PATH" => url?.path().to_string(),
+        "QUERY" => url?.query().map(|q| format!("?{}", q)).unwrap_or_default(),
+        "USERNAME" => url?.username().to_string(),
+        "PASSWORD" => url?.password().unwrap_or_default().to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepass::config::DatabaseConfig;
+
+    fn entry(title: &str, username: &str, password: &str) -> Entry {
+        let mut entry = Entry::new();
+        for (name, value) in [("Title", title), ("UserName", username), ("URL", "")] {
+            entry
+                .fields
+                .insert(name.to_string(), crate::edit::unprotected(value));
+        }
+        entry
+            .fields
+            .insert("Password".to_string(), crate::edit::protected(password));
+        entry
+    }
+
+    #[test]
+    fn test_resolves_references_and_placeholders() {
+        let mut db = Database::new(DatabaseConfig::default());
+        let canonical = entry("svc-account", "svc", "s3cret");
+        let reference = format!("{{REF:P@I:{}}}", uuid_hex(&canonical.uuid));
+        let mut shared = entry("app", "{REF:U@T:svc-account}", &reference);
+        shared.fields.insert(
+            "URL".to_string(),
+            crate::edit::unprotected("https://{USERNAME}@db.example.com:5432/app"),
+        );
+        db.root.entries.push(canonical);
+        db.root.entries.push(shared.clone());
+
+        let resolved = Resolver::new(&db).resolve_entry(&shared, &Fields::All, false);
+        assert_eq!(resolved.get_password(), Some("s3cret"));
+        assert_eq!(resolved.get_username(), Some("svc"));
+        assert_eq!(
+            resolved.get_url(),
+            Some("https://svc@db.example.com:5432/app")
+        );
+        assert!(matches!(
+            resolved.fields.get("Password"),
+            Some(Value::Protected(_))
+        ));
+
+        let resolver = Resolver::new(&db);
+        let host = resolver.resolve(
+            &resolved,
+            "{URL:HOST}:{url:port} {UNKNOWN}",
+            &mut Vec::new(),
+        );
+        assert_eq!(host, "db.example.com:5432 {UNKNOWN}");
+
+        for text in ["P@ss{word", "a{b", "x}{y", "{a}{b"] {
+            assert_eq!(resolver.resolve(&resolved, text, &mut Vec::new()), text);
+        }
+
+        let resolved = resolver.resolve_entry(&shared, &Fields::only(&["password"]), false);
+        assert_eq!(resolved.get_password(), Some("s3cret"));
+        assert_eq!(resolved.get_username(), Some("{REF:U@T:svc-account}"));
+    }
+
+    #[test]
+    fn test_leaves_cycles_and_missing_targets_as_written() {
+        let mut db = Database::new(DatabaseConfig::default());
+        let looped = entry("loop", "{PASSWORD}", "{USERNAME}");
+        let missing = entry("missing", "{REF:U@T:nothing}", "");
+        db.root.entries.push(looped.clone());
+        db.root.entries.push(missing.clone());
+
+        let resolver = Resolver::new(&db);
+        let resolved = resolver.resolve_entry(&looped, &Fields::All, false);
+        assert_eq!(resolved.get_username(), Some("{USERNAME}"));
+        assert_eq!(resolved.get_password(), Some("{PASSWORD}"));
+        let resolved = resolver.resolve_entry(&missing, &Fields::All, false);
+        assert_eq!(resolved.get_username(), Some("{REF:U@T:nothing}"));
+    }
+}
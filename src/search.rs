@@ -1,11 +1,80 @@
 use anyhow::{anyhow, Result};
-use keepass::db::{Entry, Group, Node};
+use base64::Engine;
+use keepass::db::{Entry, Group, Value};
 use keepass::Database;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use url::Url;
+use uuid::Uuid;
 
-#[derive(Debug, Clone)]
+use crate::fuzzy;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SearchOptions {
     pub case_sensitive: bool,
     pub exact_match: bool,
+    /// Fields searched by query terms without a field prefix (see
+    /// `field_name`); titles only when empty.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Search text and absolute path segments are regular expressions.
+    #[serde(default)]
+    pub regex: bool,
+    /// Search text and absolute path segments are globs; `**` in paths
+    /// matches any number of groups.
+    #[serde(default)]
+    pub glob: bool,
+    /// Search text matches when its characters appear in order.
+    #[serde(default)]
+    pub fuzzy: bool,
+    /// Tag filters that all have to match (see `matches_tags`).
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Field prefixes of the query syntax (`url:gitlab user:deploy`) and names
+/// accepted by `--in`, with the field they search.
+const FIELD_ALIASES: [(&str, &str); 7] = [
+    ("title", "Title"),
+    ("user", "UserName"),
+    ("username", "UserName"),
+    ("url", "URL"),
+    ("notes", "Notes"),
+    ("tag", TAGS),
+    ("tags", TAGS),
+];
+
+/// Pseudo field for the entry tags.
+const TAGS: &str = "Tags";
+/// Searches title, user name, URL, notes, tags and unprotected custom fields.
+const ALL_FIELDS: &str = "all";
+const STANDARD_FIELDS: [&str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
+/// One term of a query: `text` has to be found in one of `fields`.
+#[derive(Debug)]
+struct Term {
+    fields: Vec<String>,
+    text: String,
+    /// `text` compiled for --regex and --glob.
+    pattern: Option<Regex>,
+}
+
+/// A group segment of an absolute path pattern.
+enum Segment {
+    /// `**`: any number of groups.
+    AnyDepth,
+    Name(Regex),
+}
+
+/// Credential lookup by location, as used by the git and docker helpers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UrlQuery {
+    pub protocol: Option<String>,
+    /// Host name, optionally with `:port`.
+    pub host: String,
+    pub path: Option<String>,
+    pub username: Option<String>,
 }
 
 pub struct Finder<'a> {
@@ -13,10 +82,29 @@ pub struct Finder<'a> {
     options: SearchOptions,
 }
 
+/// A lookup that matched no entry, told apart from other lookup errors with
+/// `downcast_ref`.
+#[derive(Debug)]
+pub struct NotFound(pub String);
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+pub fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<NotFound>().is_some()
+}
+
 #[derive(Debug)]
 pub struct SearchResult {
     pub path: String,
     pub entry: Entry, // Return cloned Entry
+    /// Fields the query matched in; empty for path lookups.
+    pub matched: Vec<String>,
 }
 
 impl<'a> Finder<'a> {
@@ -25,10 +113,67 @@ impl<'a> Finder<'a> {
     }
 
     pub fn find(&self, query: &str) -> Result<Vec<SearchResult>> {
-        if query.starts_with('/') {
-            return self.find_by_absolute_path(query);
+        let uuid = query
+            .get(..5)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("uuid:"))
+            .map(|_| parse_uuid(&query[5..]))
+            .transpose()?;
+        let mut results = if let Some(uuid) = uuid {
+            self.find_by_uuid(&uuid)
+        } else if !query.starts_with('/') {
+            self.find_by_search(query)?
+        } else if self.options.regex || self.options.glob {
+            self.find_by_path_pattern(query)?
+        } else {
+            self.find_by_absolute_path(query)?
+        };
+        if !self.options.tags.is_empty() {
+            for filter in &self.options.tags {
+                if filter
+                    .split(',')
+                    .any(|tag| tag.trim_start_matches('!').trim().is_empty())
+                {
+                    return Err(anyhow!("Invalid tag filter: '{}'", filter));
+                }
+            }
+            results.retain(|result| matches_tags(&result.entry, &self.options.tags));
         }
-        self.find_by_search(query)
+        Ok(results)
+    }
+
+    /// Finds the entry with `uuid`; entries keep it when renamed or moved.
+    fn find_by_uuid(&self, uuid: &Uuid) -> Vec<SearchResult> {
+        let mut results = Vec::new();
+        self.for_each_entry(&mut |path, entry| {
+            if entry.uuid == *uuid {
+                results.push(SearchResult {
+                    path: path.to_string(),
+                    entry: entry.clone(),
+                    matched: Vec::new(),
+                });
+            }
+        });
+        results
+    }
+
+    /// Finds entries whose URL field points at `query.host`, best match
+    /// first: entries matching the requested path and protocol rank higher.
+    pub fn find_by_url(&self, query: &UrlQuery) -> Vec<SearchResult> {
+        let mut candidates = Vec::new();
+        self.for_each_entry(&mut |path, entry| {
+            if let Some(score) = url_match_score(entry, query) {
+                candidates.push((
+                    score,
+                    SearchResult {
+                        path: path.to_string(),
+                        entry: entry.clone(),
+                        matched: vec!["URL".to_string()],
+                    },
+                ));
+            }
+        });
+        candidates.sort_by_key(|(score, _)| Reverse(*score));
+        candidates.into_iter().map(|(_, result)| result).collect()
     }
 
     fn find_by_absolute_path(&self, path: &str) -> Result<Vec<SearchResult>> {
@@ -55,12 +200,10 @@ impl<'a> Finder<'a> {
             let mut found_group = None;
 
             // Check subgroups
-            for child in &current_group.children {
-                if let Node::Group(g) = child {
-                    if g.name == part {
-                        found_group = Some(g);
-                        break;
-                    }
+            for g in &current_group.groups {
+                if g.name == part {
+                    found_group = Some(g);
+                    break;
                 }
             }
 
@@ -74,75 +217,233 @@ impl<'a> Finder<'a> {
                 current_group = g;
             } else if is_last_part {
                 // Check entries in current_group
-                for entry in current_group.entries() {
+                for entry in &current_group.entries {
                     let title = entry.get_title().unwrap_or("");
                     if title == part {
                         return Ok(vec![SearchResult {
                             path: path.to_string(),
                             entry: entry.clone(),
+                            matched: Vec::new(),
                         }]);
                     }
                 }
-                return Err(anyhow!("Entry not found: {}", part));
+                return Err(NotFound(format!("Entry not found: {}", part)).into());
             } else {
-                return Err(anyhow!("Group not found: {}", part));
+                return Err(NotFound(format!("Group not found: {}", part)).into());
             }
         }
 
         // If we ended up at a group, maybe return all entries?
         // For now, let's say we only support finding specific entries.
-        Err(anyhow!("Path points to a group, not an entry"))
+        Err(anyhow!(
+            "Path points to a group, not an entry (use 'kpasscli ls {}' to list it)",
+            path
+        ))
     }
 
-    fn find_by_search(&self, query: &str) -> Result<Vec<SearchResult>> {
+    /// Finds the entries below groups matching the segments of an absolute
+    /// path pattern like `/Servers/*/root` or `/Customers/**/VPN`.
+    fn find_by_path_pattern(&self, path: &str) -> Result<Vec<SearchResult>> {
+        let mut parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        // If path starts with root name, skip it
+        if parts.len() > 1 && parts[0] == self.db.root.name {
+            parts.remove(0);
+        }
+        let title = parts
+            .pop()
+            .filter(|title| !title.is_empty())
+            .ok_or_else(|| anyhow!("Empty path"))?;
+        let title = self.path_pattern(title)?;
+        let segments = parts
+            .iter()
+            .map(|part| match *part {
+                "**" if self.options.glob => Ok(Segment::AnyDepth),
+                part => self.path_pattern(part).map(Segment::Name),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         let mut results = Vec::new();
-        let root = &self.db.root;
-        // Start recursion. Root name is usually not part of the path in results if we want /Group/Entry
-        // But if root has a name, it might be.
-        // Let's assume root name is not part of the path for now, or handle it inside.
-        // If we pass empty string as current_path, the logic inside handles it.
-        self.search_recursive(root, "", query, &mut results);
+        self.walk_path_pattern(&self.db.root, "", &segments, &title, &mut results);
         Ok(results)
     }
 
+    fn walk_path_pattern(
+        &self,
+        group: &Group,
+        group_path: &str,
+        segments: &[Segment],
+        title: &Regex,
+        results: &mut Vec<SearchResult>,
+    ) {
+        let subgroups = || {
+            group
+                .groups
+                .iter()
+                .filter(|g| Some(g.uuid) != self.db.meta.recyclebin_uuid)
+        };
+        match segments.split_first() {
+            None => {
+                for entry in &group.entries {
+                    let entry_title = entry.get_title().unwrap_or("");
+                    // `**` can reach the same group more than once
+                    if title.is_match(entry_title)
+                        && !results.iter().any(|r| r.entry.uuid == entry.uuid)
+                    {
+                        results.push(SearchResult {
+                            path: format!("{}/{}", group_path, entry_title),
+                            entry: entry.clone(),
+                            matched: Vec::new(),
+                        });
+                    }
+                }
+            }
+            Some((Segment::AnyDepth, rest)) => {
+                self.walk_path_pattern(group, group_path, rest, title, results);
+                for child in subgroups() {
+                    let child_path = format!("{}/{}", group_path, child.name);
+                    self.walk_path_pattern(child, &child_path, segments, title, results);
+                }
+            }
+            Some((Segment::Name(name), rest)) => {
+                for child in subgroups().filter(|g| name.is_match(&g.name)) {
+                    let child_path = format!("{}/{}", group_path, child.name);
+                    self.walk_path_pattern(child, &child_path, rest, title, results);
+                }
+            }
+        }
+    }
+
+    fn find_by_search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let default_fields = match self.options.fields.as_slice() {
+            [] => vec!["Title".to_string()],
+            names => names
+                .iter()
+                .map(|name| field_name(name))
+                .collect::<Result<_>>()?,
+        };
+        let mut terms = parse_query(query, &default_fields)?;
+        for term in &mut terms {
+            term.pattern = self.pattern(&term.text, false)?;
+        }
+
+        let mut candidates = Vec::new();
+        self.for_each_entry(&mut |path, entry| {
+            if let Some((matched, score)) = self.match_terms(entry, &terms) {
+                let depth = path.matches('/').count() as i32 - 1;
+                candidates.push((
+                    score - depth * fuzzy::DEPTH_PENALTY,
+                    SearchResult {
+                        path: path.to_string(),
+                        entry: entry.clone(),
+                        matched,
+                    },
+                ));
+            }
+        });
+        // Best match first; the sort is stable, so ties keep tree order
+        candidates.sort_by_key(|(score, _)| Reverse(*score));
+        Ok(candidates.into_iter().map(|(_, result)| result).collect())
+    }
+
+    /// Returns the fields the terms matched in and the summed score of the
+    /// best match of each term, or `None` unless every term matched.
+    fn match_terms(&self, entry: &Entry, terms: &[Term]) -> Option<(Vec<String>, i32)> {
+        let mut matched: Vec<String> = Vec::new();
+        let mut total = 0;
+        for term in terms {
+            let mut best = None;
+            for field in expand_fields(entry, &term.fields) {
+                let score = field_values(entry, &field)
+                    .iter()
+                    .filter_map(|value| self.score(value, term))
+                    .max();
+                if let Some(score) = score {
+                    best = best.max(Some(score));
+                    if !matched.contains(&field) {
+                        matched.push(field);
+                    }
+                }
+            }
+            total += best?;
+        }
+        Some((matched, total))
+    }
+
+    /// Calls `visit` with the path and entry of every entry outside the
+    /// Recycle Bin.
+    pub fn for_each_entry(&self, visit: &mut dyn FnMut(&str, &Entry)) {
+        // Root name is not part of the result paths, they look like /Group/Entry
+        self.search_recursive(&self.db.root, "", visit);
+    }
+
     fn search_recursive(
         &self,
         group: &Group,
         current_path: &str,
-        query: &str,
-        results: &mut Vec<SearchResult>,
+        visit: &mut dyn FnMut(&str, &Entry),
     ) {
-        let group_name = &group.name;
+        let group_path = child_path(current_path, &group.name);
+
+        for entry in &group.entries {
+            let title = entry.get_title().unwrap_or("");
+            let full_path = if group_path.is_empty() {
+                title.to_string()
+            } else {
+                format!("{}/{}", group_path, title)
+            };
+            visit(&format!("/{}", full_path), entry);
+        }
 
-        let group_path = if current_path.is_empty() {
-            if group_name == "Root" || group_name.is_empty() {
-                String::new()
+        for child_group in &group.groups {
+            // Deleted entries in the Recycle Bin are not search results
+            if Some(child_group.uuid) == self.db.meta.recyclebin_uuid {
+                continue;
+            }
+            self.search_recursive(child_group, &group_path, visit);
+        }
+    }
+
+    /// Compiles search text for --regex and --glob; `None` for plain text.
+    /// Globs always match the whole value, regular expressions only when
+    /// `anchored` or with --exact-match.
+    fn pattern(&self, text: &str, anchored: bool) -> Result<Option<Regex>> {
+        let source = if self.options.glob {
+            glob_to_regex(text)
+        } else if self.options.regex {
+            if anchored || self.options.exact_match {
+                format!("^(?:{})$", text)
             } else {
-                group_name.to_string()
+                text.to_string()
             }
         } else {
-            format!("{}/{}", current_path, group_name)
+            return Ok(None);
         };
+        RegexBuilder::new(&source)
+            .case_insensitive(!self.options.case_sensitive)
+            .build()
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid pattern '{}': {}", text, e))
+    }
 
-        for entry in group.entries() {
-            let title = entry.get_title().unwrap_or("");
-            if self.matches(title, query) {
-                let full_path = if group_path.is_empty() {
-                    title.to_string()
-                } else {
-                    format!("{}/{}", group_path, title)
-                };
+    /// A segment of an absolute path pattern, matching whole names.
+    fn path_pattern(&self, text: &str) -> Result<Regex> {
+        Ok(self
+            .pattern(text, true)?
+            .expect("path patterns are only used with --regex or --glob"))
+    }
 
-                results.push(SearchResult {
-                    path: format!("/{}", full_path),
-                    entry: entry.clone(),
-                });
-            }
+    /// Relevance of `value` for `term`, `None` if it does not match.
+    /// Pattern matches all rank the same.
+    fn score(&self, value: &str, term: &Term) -> Option<i32> {
+        if let Some(pattern) = &term.pattern {
+            return pattern.is_match(value).then_some(0);
         }
-
-        for child_group in group.groups() {
-            self.search_recursive(child_group, &group_path, query, results);
+        let score = fuzzy::score(value, &term.text, self.options.case_sensitive);
+        if self.options.fuzzy {
+            return score;
         }
+        self.matches(value, &term.text)
+            .then(|| score.unwrap_or_default())
     }
 
     fn matches(&self, value: &str, pattern: &str) -> bool {
@@ -163,3 +464,493 @@ impl<'a> Finder<'a> {
         }
     }
 }
+
+/// Parses an entry UUID as shown by kpasscli
+/// (`2f0d5c2e-7a4b-4e0c-9d1a-3b6c8e9f0a1b`), as 32 hex digits like in KeePass
+/// references, or in the base64 form KeePass stores in the database file.
+pub fn parse_uuid(text: &str) -> Result<Uuid> {
+    let text = text.trim();
+    if let Ok(uuid) = Uuid::parse_str(text) {
+        return Ok(uuid);
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .ok()
+        .and_then(|bytes| Uuid::from_slice(&bytes).ok())
+        .ok_or_else(|| anyhow!("Invalid UUID: {}", text))
+}
+
+/// Whether the tags of `entry` pass every filter. A filter lists
+/// alternatives separated by commas, one of which has to hold; `!tag` holds
+/// when the entry does not have the tag. Tags are compared ignoring case.
+pub fn matches_tags(entry: &Entry, filters: &[String]) -> bool {
+    let has_tag = |tag: &str| {
+        entry
+            .tags
+            .iter()
+            .any(|t| t.trim().eq_ignore_ascii_case(tag))
+    };
+    filters.iter().all(|filter| {
+        filter
+            .split(',')
+            .any(|tag| match tag.trim().strip_prefix('!') {
+                Some(tag) => !has_tag(tag.trim()),
+                None => has_tag(tag.trim()),
+            })
+    })
+}
+
+/// Maps a field alias (`user`, `url`, ...) or `all` to the field it
+/// searches; other names are custom fields. Passwords cannot be searched.
+fn field_name(name: &str) -> Result<String> {
+    if name.eq_ignore_ascii_case(ALL_FIELDS) {
+        return Ok(ALL_FIELDS.to_string());
+    }
+    if name.eq_ignore_ascii_case("Password") {
+        return Err(anyhow!("Searching the Password field is not supported"));
+    }
+    Ok(FIELD_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map_or(name, |(_, field)| field)
+        .to_string())
+}
+
+/// Splits `url:gitlab user:deploy` into terms. A query without any known
+/// field prefix is a single term searched in `default_fields`, so titles
+/// with spaces or colons keep working.
+fn parse_query(query: &str, default_fields: &[String]) -> Result<Vec<Term>> {
+    let prefixed = |token: &str| -> Option<(String, String)> {
+        let (prefix, text) = token.split_once(':')?;
+        FIELD_ALIASES
+            .iter()
+            .chain([(ALL_FIELDS, ALL_FIELDS)].iter())
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(prefix))
+            .map(|(_, field)| (field.to_string(), text.to_string()))
+    };
+
+    if !query
+        .split_whitespace()
+        .any(|token| prefixed(token).is_some())
+    {
+        return Ok(vec![Term {
+            fields: default_fields.to_vec(),
+            text: query.to_string(),
+            pattern: None,
+        }]);
+    }
+    query
+        .split_whitespace()
+        .map(|token| match prefixed(token) {
+            Some((_, text)) if text.is_empty() => {
+                Err(anyhow!("Missing search text after '{}'", token))
+            }
+            Some((field, text)) => Ok(Term {
+                fields: vec![field],
+                text,
+                pattern: None,
+            }),
+            None => Ok(Term {
+                fields: default_fields.to_vec(),
+                text: token.to_string(),
+                pattern: None,
+            }),
+        })
+        .collect()
+}
+
+/// Translates a glob (`*`, `?`, `[abc]`, `[!abc]`) into a regular
+/// expression matching the whole value.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                let mut class = chars.by_ref().take_while(|c| *c != ']').peekable();
+                if class.next_if_eq(&'!').is_some() {
+                    regex.push('^');
+                }
+                for c in class {
+                    if matches!(c, '\\' | '[') {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// Replaces `all` by the fields of `entry` it covers.
+fn expand_fields(entry: &Entry, fields: &[String]) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for field in fields {
+        if field != ALL_FIELDS {
+            expanded.push(field.clone());
+            continue;
+        }
+        expanded.extend(
+            ["Title", "UserName", "URL", "Notes", TAGS]
+                .iter()
+                .map(|f| f.to_string()),
+        );
+        let mut custom: Vec<String> = entry
+            .fields
+            .iter()
+            .filter(|(name, value)| {
+                !STANDARD_FIELDS.contains(&name.as_str()) && matches!(value, Value::Unprotected(_))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        custom.sort();
+        expanded.extend(custom);
+    }
+    expanded
+}
+
+/// Values of `field` in `entry`; custom field names ignore case.
+fn field_values<'e>(entry: &'e Entry, field: &str) -> Vec<&'e str> {
+    if field == TAGS {
+        return entry.tags.iter().map(String::as_str).collect();
+    }
+    if let Some(value) = entry.get(field) {
+        return vec![value];
+    }
+    entry
+        .fields
+        .keys()
+        .filter(|name| name.eq_ignore_ascii_case(field))
+        .filter_map(|name| entry.get(name))
+        .collect()
+}
+
+fn child_path(current_path: &str, group_name: &str) -> String {
+    if current_path.is_empty() {
+        if group_name == "Root" || group_name.is_empty() {
+            String::new()
+        } else {
+            group_name.to_string()
+        }
+    } else {
+        format!("{}/{}", current_path, group_name)
+    }
+}
+
+/// Parses an entry URL; bare host names are treated as `https://host`.
+pub fn parse_entry_url(value: &str) -> Option<Url> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if value.contains("://") {
+        Url::parse(value).ok()
+    } else {
+        Url::parse(&format!("https://{}", value)).ok()
+    }
+}
+
+/// Default port of `scheme`, e.g. 443 for `https`.
+fn default_port(scheme: &str) -> Option<u16> {
+    Url::parse(&format!("{}://host", scheme))
+        .ok()?
+        .port_or_known_default()
+}
+
+/// Scores how well the URL of `entry` matches `query`; `None` if it does not
+/// match at all (different host or port, protocol or user name).
+fn url_match_score(entry: &Entry, query: &UrlQuery) -> Option<u32> {
+    let url = parse_entry_url(entry.get_url()?)?;
+    let (host, port) = match query.host.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse::<u16>().ok()),
+        None => (query.host.as_str(), None),
+    };
+    if !url.host_str()?.eq_ignore_ascii_case(host) {
+        return None;
+    }
+    if let Some(port) = port.or_else(|| default_port(query.protocol.as_deref()?)) {
+        // Bare host names take the default port of the query protocol
+        let entry_port = if entry.get_url()?.contains("://") {
+            url.port_or_known_default()
+        } else {
+            url.port()
+                .or_else(|| default_port(query.protocol.as_deref()?))
+        };
+        if entry_port != Some(port) {
+            return None;
+        }
+    }
+    if let Some(username) = &query.username {
+        if entry
+            .get_username()
+            .is_some_and(|u| !u.is_empty() && u != username)
+        {
+            return None;
+        }
+    }
+
+    let mut score = 1;
+    if let Some(protocol) = &query.protocol {
+        if url.scheme().eq_ignore_ascii_case(protocol) {
+            score += 1;
+        } else if entry.get_url()?.contains("://") {
+            return None;
+        }
+    }
+    let entry_path = url.path().trim_matches('/');
+    if let Some(path) = &query.path {
+        let path = path.trim_matches('/');
+        if !entry_path.is_empty() {
+            if path == entry_path || path.starts_with(&format!("{}/", entry_path)) {
+                // Longer matching paths are more specific
+                score += 2 + entry_path.len() as u32;
+            } else {
+                return None;
+            }
+        }
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn terms(query: &str, default_fields: &[String]) -> Vec<(Vec<String>, String)> {
+        parse_query(query, default_fields)
+            .unwrap()
+            .into_iter()
+            .map(|term| (term.fields, term.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let title = fields(&["Title"]);
+        assert_eq!(
+            terms("My Server: prod", &title),
+            vec![(title.clone(), "My Server: prod".to_string())]
+        );
+        assert_eq!(
+            terms("url:gitlab User:deploy admin", &title),
+            vec![
+                (fields(&["URL"]), "gitlab".to_string()),
+                (fields(&["UserName"]), "deploy".to_string()),
+                (title, "admin".to_string()),
+            ]
+        );
+        assert!(parse_query("url: gitlab", &[]).is_err());
+        assert!(field_name("password").is_err());
+    }
+
+    #[test]
+    fn test_match_terms_reports_fields() {
+        let db = Database::new(Default::default());
+        let finder = Finder::new(&db, SearchOptions::default());
+        let mut entry = Entry::new();
+        entry
+            .fields
+            .insert("Title".to_string(), Value::Unprotected("admin".to_string()));
+        entry.fields.insert(
+            "URL".to_string(),
+            Value::Unprotected("https://gitlab.example.com".to_string()),
+        );
+        entry.fields.insert(
+            "Environment".to_string(),
+            Value::Unprotected("production".to_string()),
+        );
+        entry.tags = vec!["ci".to_string()];
+
+        let terms = parse_query("url:GITLAB tag:ci", &[]).unwrap();
+        assert_eq!(
+            finder
+                .match_terms(&entry, &terms)
+                .map(|(matched, _)| matched),
+            Some(fields(&["URL", "Tags"]))
+        );
+        let terms = parse_query("prod", &fields(&["all"])).unwrap();
+        assert_eq!(
+            finder
+                .match_terms(&entry, &terms)
+                .map(|(matched, _)| matched),
+            Some(fields(&["Environment"]))
+        );
+        let terms = parse_query("url:gitlab user:deploy", &[]).unwrap();
+        assert_eq!(
+            finder
+                .match_terms(&entry, &terms)
+                .map(|(matched, _)| matched),
+            None
+        );
+    }
+
+    #[test]
+    fn test_path_patterns() {
+        use keepass::db::Group;
+
+        let mut db = Database::new(Default::default());
+        for (customer, nested) in [("acme", false), ("globex", true)] {
+            let mut vpn = Entry::new();
+            vpn.fields
+                .insert("Title".to_string(), Value::Unprotected("VPN".to_string()));
+            let mut group = Group::new(customer);
+            if nested {
+                let mut office = Group::new("Office");
+                office.entries.push(vpn);
+                group.groups.push(office);
+            } else {
+                group.entries.push(vpn);
+            }
+            let mut customers = Group::new("Customers");
+            customers.groups.push(group);
+            db.root.groups.push(customers);
+        }
+
+        let find = |options: SearchOptions, query: &str| -> Vec<String> {
+            let finder = Finder::new(&db, options);
+            let mut paths: Vec<String> = finder
+                .find(query)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.path)
+                .collect();
+            paths.sort();
+            paths
+        };
+        let glob = SearchOptions {
+            glob: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            find(glob.clone(), "/Customers/**/vpn"),
+            vec!["/Customers/acme/VPN", "/Customers/globex/Office/VPN"]
+        );
+        assert_eq!(
+            find(glob.clone(), "/Customers/*/V?N"),
+            vec!["/Customers/acme/VPN"]
+        );
+        assert_eq!(find(glob, "/Customers/[!a]*/*/VPN").len(), 1);
+        let regex = SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            find(regex.clone(), "/Customers/(acme|globex)/VPN"),
+            vec!["/Customers/acme/VPN"]
+        );
+        assert_eq!(find(regex, "^v.n$").len(), 2);
+
+        let finder = Finder::new(&db, SearchOptions::default());
+        for missing in ["/Customers/acme/SSH", "/Customers/initech/VPN"] {
+            assert!(is_not_found(&finder.find(missing).unwrap_err()));
+        }
+    }
+
+    #[test]
+    fn test_results_ranked() {
+        use keepass::db::Group;
+
+        let entry = |title: &str| {
+            let mut entry = Entry::new();
+            entry
+                .fields
+                .insert("Title".to_string(), Value::Unprotected(title.to_string()));
+            entry
+        };
+        let mut db = Database::new(Default::default());
+        let mut nested = Group::new("Deep");
+        nested.entries.push(entry("gitlab"));
+        let mut group = Group::new("Dev");
+        group.entries.push(entry("old gitlab"));
+        group.groups.push(nested);
+        db.root.entries.push(entry("mygitlab"));
+        db.root.groups.push(group);
+        db.root.entries.push(entry("gitlab-runner"));
+
+        let paths = |options: SearchOptions, query: &str| -> Vec<String> {
+            let finder = Finder::new(&db, options);
+            finder
+                .find(query)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.path)
+                .collect()
+        };
+        assert_eq!(
+            paths(SearchOptions::default(), "gitlab"),
+            vec![
+                "/Dev/Deep/gitlab",
+                "/gitlab-runner",
+                "/Dev/old gitlab",
+                "/mygitlab"
+            ]
+        );
+        let fuzzy = SearchOptions {
+            fuzzy: true,
+            ..Default::default()
+        };
+        assert_eq!(paths(fuzzy.clone(), "gl run"), vec!["/gitlab-runner"]);
+        assert_eq!(paths(fuzzy, "gtlb").len(), 4);
+    }
+
+    #[test]
+    fn test_matches_tags() {
+        let mut entry = Entry::new();
+        entry.tags = vec!["prod".to_string(), "Team-A".to_string()];
+        let filters = |filters: &[&str]| -> bool {
+            let filters: Vec<String> = filters.iter().map(|f| f.to_string()).collect();
+            matches_tags(&entry, &filters)
+        };
+        assert!(filters(&["prod", "team-a"]));
+        assert!(filters(&["staging,prod"]));
+        assert!(filters(&["!legacy"]));
+        assert!(!filters(&["prod", "!team-a"]));
+        assert!(!filters(&["staging"]));
+        assert!(filters(&["staging,!legacy"]));
+    }
+
+    #[test]
+    fn test_parse_uuid() {
+        let uuid = Uuid::parse_str("46c9b1ff-bd4a-bc4b-bb26-0c6190bad20c").unwrap();
+        assert_eq!(
+            parse_uuid("46C9B1FFBD4ABC4BBB260C6190BAD20C").unwrap(),
+            uuid
+        );
+        assert_eq!(parse_uuid("Rsmx/71KvEu7JgxhkLrSDA==").unwrap(), uuid);
+        assert!(parse_uuid("not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn test_url_match_default_port() {
+        let entry = |url: &str| {
+            let mut entry = Entry::new();
+            entry
+                .fields
+                .insert("URL".to_string(), Value::Unprotected(url.to_string()));
+            entry
+        };
+        let query = |protocol: &str, host: &str| UrlQuery {
+            protocol: Some(protocol.to_string()),
+            host: host.to_string(),
+            ..Default::default()
+        };
+        let https = query("https", "git.example.com:443");
+        assert!(url_match_score(&entry("https://git.example.com"), &https).is_some());
+        assert!(url_match_score(&entry("git.example.com"), &https).is_some());
+        assert!(url_match_score(&entry("https://git.example.com:8443"), &https).is_none());
+        let http = query("http", "git.example.com");
+        assert!(url_match_score(&entry("http://git.example.com:80/"), &http).is_some());
+        assert!(url_match_score(&entry("git.example.com:8080"), &http).is_none());
+    }
+}